@@ -0,0 +1,41 @@
+//! Implements the `/autoshuffle` command.
+//!
+//! Lets server admins choose whether playlists added with `/play` are shuffled,
+//! overriding the default from the config.
+//! The override only lasts until the bot restarts.
+
+use tracing::instrument;
+
+use super::play;
+use crate::data::GetData;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Set whether playlists are shuffled when added. Leave empty to use the default.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 2,
+    required_permissions = "MANAGE_GUILD",
+    rename = "autoshuffle"
+)]
+pub async fn auto_shuffle(
+    ctx: Context<'_>,
+    #[description = "Shuffle the tracks of playlists added with /play"] enabled: Option<bool>,
+) -> Result<(), ParakeetError> {
+    {
+        let guild_data = ctx.guild_data().await?;
+        guild_data.lock().await.auto_shuffle_playlists = enabled;
+    }
+
+    let reply = match play::auto_shuffle_playlists(&ctx).await? {
+        true => "Playlists are now shuffled when added.",
+        false => "Playlists are now added in order.",
+    };
+    tracing::info!("{reply}");
+    ctx.reply(reply).await?;
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+//! Implements the `/dedupe` command.
+//!
+//! Removes tracks that were queued several times in a row, keeping one of each.
+//! Repeats that aren't right after each other are left alone, unless `all` is set.
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Remove tracks repeated in a row, keeping the first one.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 5)]
+pub async fn dedupe(
+    ctx: Context<'_>,
+    #[description = "Also remove repeats that aren't in a row"] all: Option<bool>,
+) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    // The first of each is kept, so the current track is never removed.
+    let removed = if all.unwrap_or(false) {
+        lib::call::remove_duplicates(&call, &queue_meta).await?
+    } else {
+        lib::call::remove_consecutive_duplicates(&call, &queue_meta).await?
+    };
+    tracing::info!("Removed {removed} duplicate tracks");
+    ctx.reply(removed_reply(removed)).await?;
+
+    Ok(())
+}
+
+/// The reply after removing `removed` duplicate tracks.
+pub(super) fn removed_reply(removed: usize) -> String {
+    match removed {
+        0 => "No duplicates found, every track in the queue is different!".to_string(),
+        1 => "Removed 1 duplicate track.".to_string(),
+        removed => format!("Removed {removed} duplicate tracks."),
+    }
+}
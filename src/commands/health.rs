@@ -0,0 +1,82 @@
+//! Implements the `/health` command.
+//!
+//! The bot responds with an embed summarizing its state across every guild:
+//! its uptime, the voice calls it's in, how many tracks are queued, and its memory use.
+//! Unlike `/status`, this is about the bot process rather than a single guild,
+//! so only the bot's owners can use it.
+
+use std::time::Duration;
+
+use poise::CreateReply;
+use serenity::CreateEmbed;
+use tracing::instrument;
+
+use crate::lib;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Show how the bot as a whole is doing
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, owners_only, guild_cooldown = 5)]
+pub async fn health(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let uptime = ctx.data().started.elapsed();
+
+    // Calls are kept around after leaving, so only count the ones in a channel.
+    let mut connections = 0;
+    for (_, call) in lib::call::get_manager(&ctx).await?.iter() {
+        if call.lock().await.current_channel().is_some() {
+            connections += 1;
+        }
+    }
+
+    // Don't keep the map locked while locking each guild.
+    let guilds: Vec<_> = ctx
+        .data()
+        .guild_data
+        .lock()
+        .await
+        .values()
+        .cloned()
+        .collect();
+    let mut queued = 0;
+    for guild_data in guilds {
+        let queue_meta = guild_data.lock().await.live_queue();
+        queued += queue_meta.len().await;
+    }
+
+    let memory = match resident_memory() {
+        Some(bytes) => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+        None => "Unknown".to_string(),
+    };
+
+    let embed = CreateEmbed::default()
+        .title("Health")
+        .field("Uptime", format_uptime(uptime), true)
+        .field("Voice calls", connections.to_string(), true)
+        .field("Queued tracks", queued.to_string(), true)
+        .field("Memory", memory, true);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Format `uptime` as e.g. `2d 3h 14m`, leaving out leading zeroes.
+fn format_uptime(uptime: Duration) -> String {
+    let total_mins = uptime.as_secs() / 60;
+    let (days, hours, mins) = (total_mins / 1440, total_mins / 60 % 24, total_mins % 60);
+    match (days, hours) {
+        (0, 0) => format!("{mins}m"),
+        (0, _) => format!("{hours}h {mins}m"),
+        _ => format!("{days}d {hours}h {mins}m"),
+    }
+}
+
+/// The memory the process currently uses in bytes, only known on linux.
+fn resident_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    // Looks like "VmRSS:     12345 kB".
+    let kilobytes: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
@@ -0,0 +1,142 @@
+//! Implements the `/history` command.
+//!
+//! Without a track, the bot lists the recently finished tracks.
+//! With one, that track is added to the queue again, either right after the current
+//! track or at the back.
+
+use poise::CreateReply;
+use serenity::AutocompleteChoice;
+use serenity::CreateEmbed;
+use tracing::instrument;
+
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::data::TrackMetadata;
+use crate::error::UserError;
+use crate::lib;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Discord allows at most this many autocomplete choices.
+const MAX_CHOICES: usize = 25;
+
+/// Discord allows autocomplete choice names of at most this many chars.
+const MAX_CHOICE_LEN: usize = 100;
+
+/// Where a track from the history goes in the queue.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+enum Placement {
+    /// Right after the current track.
+    Front,
+    /// The end of the queue.
+    #[default]
+    Back,
+}
+
+/// Autocompletes finished tracks whose title contains `input`, by their history position.
+async fn autocomplete_history(ctx: Context<'_>, input: &str) -> Vec<AutocompleteChoice> {
+    let Ok(guild_data) = ctx.guild_data().await else {
+        return vec![];
+    };
+    let input = input.to_lowercase();
+    let guild_data = guild_data.lock().await;
+
+    guild_data
+        .history
+        .iter()
+        .enumerate()
+        .filter_map(|(num, track)| {
+            let title = track
+                .title
+                .clone()
+                .unwrap_or_else(|| missing_title().to_string());
+            if !title.to_lowercase().contains(&input) {
+                return None;
+            }
+            let name: String = format!("{num}. {title}")
+                .chars()
+                .take(MAX_CHOICE_LEN)
+                .collect();
+            Some(AutocompleteChoice::new(name, num.to_string()))
+        })
+        .take(MAX_CHOICES)
+        .collect()
+}
+
+/// Show recently played tracks, or play one again.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "A finished track to add again"]
+    #[autocomplete = "autocomplete_history"]
+    track: Option<String>,
+    #[description = "Where to add it, the back by default"] placement: Option<Placement>,
+) -> Result<(), ParakeetError> {
+    let history = ctx.guild_data().await?.lock().await.history.clone();
+
+    let Some(track) = track else {
+        let embed = CreateEmbed::default()
+            .title("Recently played")
+            .description(list_history(history.iter()));
+        ctx.send(CreateReply::default().embed(embed)).await?;
+        return Ok(());
+    };
+
+    let bad_args = || UserError::BadArgs {
+        input: Some(track.clone()),
+    };
+    let index: usize = track.parse().map_err(|_| bad_args())?;
+    let meta = history.get(index).cloned().ok_or_else(bad_args)?;
+    let title = meta
+        .title
+        .clone()
+        .unwrap_or_else(|| missing_title().to_string());
+
+    // Tracks without an url can't be re-created.
+    let Some(url) = meta.url.clone() else {
+        ctx.reply(format!("`{title}` can't be played again."))
+            .await?;
+        return Ok(());
+    };
+
+    let call = lib::call::join_author(&ctx).await?;
+    let input = lib::call::rebuild_input(ctx.http_client().await, url);
+    let handle = match placement.unwrap_or_default() {
+        Placement::Front => lib::call::enqueue_front(&ctx, &call, input, meta.to_aux()).await?,
+        Placement::Back => lib::call::enqueue_known(&ctx, &call, input, meta.to_aux()).await?,
+    };
+
+    // Found by id, in case something else was added meanwhile.
+    let queue_meta = ctx.queue_meta().await?;
+    let position = queue_meta.position(handle.uuid()).await.unwrap_or_default();
+
+    tracing::info!("Re-adding {title} from history at {position}");
+    ctx.reply(format!("Added `{title}` again at position {position}."))
+        .await?;
+
+    Ok(())
+}
+
+/// List `tracks` by their position in the history.
+fn list_history<'a>(tracks: impl Iterator<Item = &'a TrackMetadata>) -> String {
+    let lines: Vec<String> = tracks
+        .enumerate()
+        .map(|(num, track)| format!("`{num}.` {track}"))
+        .collect();
+    if lines.is_empty() {
+        return "Nothing has finished playing yet!".to_string();
+    }
+
+    // An embed has a limit of 4096 chars
+    let mut buffer = String::new();
+    for line in lines {
+        if buffer.len() + line.len() + 1 > 4096 {
+            break;
+        }
+        buffer.push_str(&line);
+        buffer.push('\n');
+    }
+    buffer
+}
@@ -0,0 +1,79 @@
+//! Implements the `/import` command.
+//!
+//! The bot reads an m3u playlist file, like the ones from `/queue export`, and queues its tracks.
+
+use poise::CreateReply;
+use songbird::input::Input;
+use songbird::input::YoutubeDl;
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::lib::call;
+use crate::lib::m3u;
+use crate::lib::youtube::PLAYLIST_LIMIT;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Playlist files bigger than this many bytes aren't downloaded.
+/// Even [PLAYLIST_LIMIT] long urls fit easily.
+const MAX_FILE_SIZE: u32 = 1024 * 1024;
+
+/// Queue the tracks from an m3u playlist file.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 10)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "An m3u/m3u8 playlist file"] file: serenity::Attachment,
+) -> Result<(), ParakeetError> {
+    if file.size > MAX_FILE_SIZE {
+        Err(UserError::FileTooLarge {
+            limit: MAX_FILE_SIZE,
+        })?
+    }
+    let content = file.download().await?;
+    let content = String::from_utf8_lossy(&content);
+
+    let entries = m3u::parse_m3u(&content);
+    if entries.is_empty() {
+        Err(UserError::BadArgs {
+            input: Some(file.filename.clone()),
+        })?
+    }
+
+    let http_client = ctx.http_client().await;
+
+    // Join the user's call
+    let call = call::join_author(&ctx).await?;
+    lib::defer(&ctx).await;
+
+    let (urls, inputs): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .take(PLAYLIST_LIMIT)
+        .map(|m3u::Entry { url, title }| {
+            let input: Input = YoutubeDl::new(http_client.clone(), url.to_string()).into();
+            (url, (input, title))
+        })
+        .unzip();
+
+    let enqueued = call::enqueue_all(&ctx, &call, inputs).await?;
+    for (num, e) in &enqueued.failed {
+        tracing::debug!("Skipping entry {num} '{}': {e}", urls[*num]);
+    }
+
+    let added = enqueued.added.len();
+    tracing::info!("Imported {added} tracks from {}", file.filename);
+    let mut reply = format!("Added {added} tracks from `{}`.", file.filename);
+    if !enqueued.failed.is_empty() {
+        let skipped = enqueued.failed.len();
+        reply.push_str(&format!(" Skipped {skipped} unavailable."));
+    }
+    if let Some(note) = enqueued.overflow_note() {
+        reply.push_str(&format!("\n{note}"));
+    }
+    lib::send_late(&ctx, CreateReply::default().content(reply)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! Implements the `/join` command.
+//!
+//! The bot joins the author's voice channel without playing anything,
+//! moving over if it's in another channel.
+
+use tracing::instrument;
+
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Join your voice channel.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn join(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let channel_id = lib::call::author_channel(&ctx)?;
+    if lib::call::bot_channel(&ctx).await? == Some(channel_id) {
+        ctx.reply(format!("Already in <#{channel_id}>.")).await?;
+        return Ok(());
+    }
+
+    lib::call::join_author(&ctx).await?;
+    ctx.reply(format!("Joined <#{channel_id}>.")).await?;
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+//! Implements the `/joinactive` command.
+//!
+//! The bot joins whichever voice channel has the most (non-bot) members.
+//! Ties go to the author's channel, then to the channel with the lowest id.
+
+use std::collections::HashMap;
+
+use futures::future::join_all;
+use serenity::ChannelId;
+use serenity::UserId;
+use tracing::instrument;
+
+use crate::error::UserError;
+use crate::lib;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Join the voice channel with the most members.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 5,
+    rename = "joinactive"
+)]
+pub async fn join_active(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let Some((channel_id, members)) = most_active_channel(&ctx).await? else {
+        ctx.reply("Nobody is in a voice channel right now.").await?;
+        return Ok(());
+    };
+
+    lib::call::join_channel(&ctx, channel_id).await?;
+
+    let s = if members == 1 { "" } else { "s" };
+    ctx.reply(format!("Joined <#{channel_id}> with {members} member{s}."))
+        .await?;
+
+    Ok(())
+}
+
+/// Find the voice channel with the most non-bot members, and how many there are.
+async fn most_active_channel(
+    ctx: &Context<'_>,
+) -> Result<Option<(ChannelId, usize)>, ParakeetError> {
+    let bot_id = ctx.cache().current_user().id;
+
+    // Who is in which channel, and whether they're a bot if the cache knows.
+    let (guild_id, author_channel, occupants): (_, _, Vec<(ChannelId, UserId, Option<bool>)>) = {
+        let guild = ctx.guild().ok_or(UserError::NotInGuild)?;
+        let occupants = guild
+            .voice_states
+            .values()
+            .filter(|vs| vs.user_id != bot_id)
+            .filter_map(|vs| {
+                let channel_id = vs.channel_id?;
+                let is_bot = vs
+                    .member
+                    .as_ref()
+                    .or_else(|| guild.members.get(&vs.user_id))
+                    .map(|member| member.user.bot);
+                Some((channel_id, vs.user_id, is_bot))
+            })
+            .collect();
+        let author_channel = guild
+            .voice_states
+            .get(&ctx.author().id)
+            .and_then(|vs| vs.channel_id);
+        (guild.id, author_channel, occupants)
+    };
+
+    // Fall back to asking discord about members missing from the cache, all at once.
+    let lookups = occupants
+        .into_iter()
+        .map(|(channel_id, user_id, is_bot)| async move {
+            let is_bot = match is_bot {
+                Some(is_bot) => is_bot,
+                // Not worth failing over, so they're counted as members.
+                None => match guild_id.member(ctx, user_id).await {
+                    Ok(member) => member.user.bot,
+                    Err(e) => {
+                        tracing::warn!("Failed to look up member {user_id}: {e}");
+                        false
+                    }
+                },
+            };
+            (channel_id, is_bot)
+        });
+
+    let mut counts: HashMap<ChannelId, usize> = HashMap::new();
+    for (channel_id, is_bot) in join_all(lookups).await {
+        if !is_bot {
+            *counts.entry(channel_id).or_default() += 1;
+        }
+    }
+
+    Ok(pick_channel(counts, author_channel))
+}
+
+/// The channel with the most members in `counts`, along with how many.
+/// Ties go to `author_channel`, then to the lowest channel id.
+fn pick_channel(
+    counts: HashMap<ChannelId, usize>,
+    author_channel: Option<ChannelId>,
+) -> Option<(ChannelId, usize)> {
+    counts.into_iter().max_by_key(|&(channel_id, members)| {
+        (
+            members,
+            Some(channel_id) == author_channel,
+            std::cmp::Reverse(channel_id),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ties_prefer_author_then_lowest_id() {
+        let counts = HashMap::from([
+            (ChannelId::new(3), 2),
+            (ChannelId::new(2), 2),
+            (ChannelId::new(5), 2),
+            (ChannelId::new(1), 1),
+        ]);
+        assert_eq!(
+            pick_channel(counts.clone(), None),
+            Some((ChannelId::new(2), 2))
+        );
+        assert_eq!(
+            pick_channel(counts.clone(), Some(ChannelId::new(5))),
+            Some((ChannelId::new(5), 2))
+        );
+        // More members still win over the author's channel.
+        assert_eq!(
+            pick_channel(counts, Some(ChannelId::new(1))),
+            Some((ChannelId::new(2), 2))
+        );
+        assert_eq!(pick_channel(HashMap::new(), None), None);
+    }
+}
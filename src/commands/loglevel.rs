@@ -0,0 +1,38 @@
+//! Implements the `/loglevel` command.
+//!
+//! Changes how verbose the logs are without restarting, which helps with live troubleshooting.
+//! The level from the config is used again after a restart.
+
+use tracing::instrument;
+use tracing::level_filters::LevelFilter;
+
+use crate::error::UserError;
+use crate::log;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Change the log level until the bot restarts.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, owners_only, rename = "loglevel")]
+pub async fn log_level(
+    ctx: Context<'_>,
+    #[description = "One of off/error/warn/info/debug/trace"] level: String,
+) -> Result<(), ParakeetError> {
+    let filter: LevelFilter = level.parse().map_err(|_| UserError::BadArgs {
+        input: Some(level.clone()),
+    })?;
+
+    let data = ctx.data();
+    let handle = data
+        .log_handle
+        .as_ref()
+        .ok_or(ParakeetError::MissingFromSetup {
+            reason: "Expecting a log handle.".to_string(),
+        })?;
+    log::set_level(handle, &data.config, filter)?;
+
+    tracing::info!("Log level set to {filter}");
+    ctx.reply(format!("Log level set to `{filter}`.")).await?;
+
+    Ok(())
+}
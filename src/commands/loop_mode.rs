@@ -0,0 +1,45 @@
+//! Implements the `/loop` command.
+//!
+//! Without a mode, the loop mode cycles through off, track, and queue.
+//! See [LoopMode] for what each mode does.
+
+use poise::ChoiceParameter;
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::data::LoopMode;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Repeat the current track or the whole queue.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 2,
+    rename = "loop"
+)]
+pub async fn loop_mode(
+    ctx: Context<'_>,
+    #[description = "What to repeat, cycles if not given"] mode: Option<LoopMode>,
+) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+
+    let mode = {
+        let guild_data = ctx.guild_data().await?;
+        let mut guild_data = guild_data.lock().await;
+        let mode = mode.unwrap_or(guild_data.loop_mode.next());
+        guild_data.loop_mode = mode;
+        mode
+    };
+    lib::call::set_loop_mode(&call, mode).await?;
+
+    tracing::info!("Loop mode set to {}", mode.name());
+    ctx.reply(format!("Loop mode set to `{}`.", mode.name()))
+        .await?;
+
+    Ok(())
+}
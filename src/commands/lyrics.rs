@@ -0,0 +1,59 @@
+//! Implements the `/lyrics` command.
+//!
+//! The bot looks up the lyrics of the current track and sends them as embeds.
+//! Only registered if [Config::lyrics_enabled](crate::Config::lyrics_enabled) is set,
+//! since it calls an external api.
+
+use poise::CreateReply;
+use serenity::CreateEmbed;
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::lib::lyrics;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// An embed has a limit of 4096 chars.
+const MAX_EMBED_LEN: usize = 4096;
+
+/// Show the lyrics of the current track
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 5)]
+pub async fn lyrics(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let track = ctx
+        .live_queue_meta()
+        .await?
+        .front()
+        .await
+        .ok_or(UserError::EmptyQueue)?;
+    let Some(title) = track.title else {
+        ctx.reply("No lyrics found, the current track has no title.")
+            .await?;
+        return Ok(());
+    };
+
+    lib::defer(&ctx).await;
+
+    let (artist, song) = lyrics::guess_artist_title(&title, track.channel.as_deref());
+    tracing::info!("Looking up lyrics for {song} by {artist}");
+    let Some(text) = lyrics::lookup(&ctx.http_client().await, &artist, &song).await? else {
+        ctx.reply(format!("No lyrics found for `{song}` by `{artist}`."))
+            .await?;
+        return Ok(());
+    };
+
+    // Messages can't hold more than 6000 chars of embeds, so each chunk gets its own.
+    for (num, chunk) in lyrics::chunk(&text, MAX_EMBED_LEN).into_iter().enumerate() {
+        let mut embed = CreateEmbed::default().description(chunk);
+        if num == 0 {
+            let title = format!("{song} - {artist}");
+            embed = embed.title(lib::truncate_title(&title, lib::MAX_TITLE_LEN));
+        }
+        ctx.send(CreateReply::default().embed(embed)).await?;
+    }
+
+    Ok(())
+}
@@ -1,9 +1,41 @@
 //! Bot commands.
 
+mod autoshuffle;
+mod dedupe;
+mod health;
+mod history;
+mod import;
+mod join;
+mod joinactive;
+mod loglevel;
+mod loop_mode;
+mod lyrics;
+mod move_track;
+mod nowplaying;
+mod ping;
 mod play;
+mod playall;
+mod preview;
 mod queue;
+mod queuefit;
+mod queuelimit;
+mod remove;
+mod remove_dupes;
+mod replay;
+mod resume;
+mod search;
+mod searchstrategy;
+mod seek;
+mod session;
+mod shuffle;
 mod skip;
+mod skipto;
+mod sound;
+mod status;
 mod stop;
+mod transferqueue;
+mod volume;
+mod wait;
 
 use crate::{Data, ParakeetError};
 
@@ -14,9 +46,42 @@ pub type Command = poise::Command<Data, ParakeetError>;
 pub fn list() -> Vec<Command> {
     vec![
         play::play(),
+        play::play_next(),
         play::play_file(),
+        playall::play_all(),
         skip::skip(),
+        skipto::skip_to(),
+        resume::resume(),
+        seek::seek(),
         stop::stop(),
         queue::queue(),
+        nowplaying::now_playing(),
+        lyrics::lyrics(),
+        queuelimit::queue_limit(),
+        queuefit::queue_fit(),
+        volume::volume(),
+        loop_mode::loop_mode(),
+        preview::preview(),
+        sound::sound(),
+        remove::remove(),
+        dedupe::dedupe(),
+        remove_dupes::remove_dupes(),
+        shuffle::shuffle(),
+        session::session(),
+        history::history(),
+        replay::replay(),
+        status::status(),
+        health::health(),
+        ping::ping(),
+        search::search(),
+        searchstrategy::search_strategy(),
+        autoshuffle::auto_shuffle(),
+        import::import(),
+        join::join(),
+        joinactive::join_active(),
+        transferqueue::transfer_queue(),
+        wait::wait(),
+        loglevel::log_level(),
+        move_track::move_track(),
     ]
 }
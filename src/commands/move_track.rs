@@ -0,0 +1,114 @@
+//! Implements the `/move` command.
+//!
+//! Tracks are moved to a queue position, or to one of `top`, `bottom`, `up` or `down`.
+//! The current track (position 0) stays where it is.
+
+use std::str::FromStr;
+
+use tracing::instrument;
+
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Where to move a track to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Destination {
+    /// A queue position.
+    Index(usize),
+    /// Right after the current track.
+    Top,
+    /// The end of the queue.
+    Bottom,
+    /// One position closer to the current track.
+    Up,
+    /// One position further from the current track.
+    Down,
+}
+
+impl FromStr for Destination {
+    type Err = UserError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "top" => Ok(Destination::Top),
+            "bottom" => Ok(Destination::Bottom),
+            "up" => Ok(Destination::Up),
+            "down" => Ok(Destination::Down),
+            other => other
+                .parse()
+                .map(Destination::Index)
+                .map_err(|_| UserError::BadArgs {
+                    input: Some(s.to_string()),
+                }),
+        }
+    }
+}
+
+impl Destination {
+    /// The queue position of this destination for a track at `from` in a queue of length `len`.
+    /// `up` and `down` stop at either end of the queue, after the current track.
+    fn resolve(self, from: usize, len: usize) -> usize {
+        let last = len.saturating_sub(1).max(1);
+        match self {
+            Destination::Index(index) => index,
+            Destination::Top => 1,
+            Destination::Bottom => last,
+            Destination::Up => from.saturating_sub(1).max(1),
+            Destination::Down => (from + 1).min(last),
+        }
+    }
+}
+
+/// Move a track to another position in the queue.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 2,
+    rename = "move"
+)]
+pub async fn move_track(
+    ctx: Context<'_>,
+    #[description = "Position of the track to move"] from: usize,
+    #[description = "Position to move to, or one of top/bottom/up/down"] to: String,
+) -> Result<(), ParakeetError> {
+    let destination: Destination = to.parse()?;
+
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    let to = destination.resolve(from, queue_meta.len().await);
+    let moved = lib::call::move_track(&call, &queue_meta, from, to).await?;
+
+    let title = moved.title.unwrap_or_else(|| missing_title().to_string());
+    tracing::info!("Moving {title} from {from} to {to}");
+    ctx.reply(format!("Moved `{title}` to position {to}."))
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn destinations_resolve() {
+        let parse = |s: &str| s.parse::<Destination>().unwrap();
+
+        assert_eq!(parse("3").resolve(1, 5), 3);
+        assert_eq!(parse("Top").resolve(4, 5), 1);
+        assert_eq!(parse("bottom").resolve(1, 5), 4);
+        assert_eq!(parse("up").resolve(3, 5), 2);
+        assert_eq!(parse("up").resolve(1, 5), 1);
+        assert_eq!(parse("down").resolve(3, 5), 4);
+        assert_eq!(parse("down").resolve(4, 5), 4);
+        assert!("sideways".parse::<Destination>().is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! Implements the `/nowplaying` command.
+//!
+//! The bot responds with an embed of the current track and how far along it is,
+//! as a progress bar.
+
+use std::time::Duration;
+
+use poise::CreateReply;
+use serenity::CreateEmbed;
+use tracing::instrument;
+
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// How many segments the progress bar has.
+const BAR_LEN: usize = 15;
+
+/// Show the current track
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 2,
+    rename = "nowplaying"
+)]
+pub async fn now_playing(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let queue_meta = ctx.live_queue_meta().await?;
+    let track = queue_meta.front().await.ok_or(UserError::EmptyQueue)?;
+    let elapsed = lib::call::current_position(&ctx).await?;
+
+    let title = match track.title {
+        Some(title) => lib::truncate_title(&title, lib::MAX_TITLE_LEN),
+        None => missing_title().to_string(),
+    };
+    let mut embed = CreateEmbed::default().title(title);
+
+    // Make title link to url if available.
+    if let Some(url) = track.url {
+        embed = embed.url(url);
+    }
+    if let Some(thumbnail) = track.thumbnail_url {
+        embed = embed.thumbnail(thumbnail)
+    }
+
+    let progress = match track.duration {
+        Some(total) => format!(
+            "{} {} {}",
+            lib::format_duration(&elapsed),
+            progress_bar(elapsed, total),
+            lib::format_duration(&total)
+        ),
+        None => lib::format_duration(&elapsed),
+    };
+    embed = embed.description(progress);
+
+    if let Some(channel) = track.channel {
+        embed = embed.field("Channel", channel, true);
+    }
+    if let Some(user) = track.requested_by {
+        embed = embed.field("Requested by", format!("<@{user}>"), true);
+    }
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Render how far `elapsed` is into `total` as a bar, like `▬▬🔘▬▬`.
+fn progress_bar(elapsed: Duration, total: Duration) -> String {
+    let ratio = if total.is_zero() {
+        0.0
+    } else {
+        (elapsed.as_secs_f64() / total.as_secs_f64()).min(1.0)
+    };
+    let knob = (ratio * (BAR_LEN - 1) as f64).round() as usize;
+
+    (0..BAR_LEN)
+        .map(|i| if i == knob { "🔘" } else { "▬" })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knob_follows_progress() {
+        let total = Duration::from_secs(140);
+        let knob_at = |secs| {
+            progress_bar(Duration::from_secs(secs), total)
+                .chars()
+                .position(|c| c == '🔘')
+        };
+
+        assert_eq!(knob_at(0), Some(0));
+        assert_eq!(knob_at(70), Some(7));
+        assert_eq!(knob_at(140), Some(BAR_LEN - 1));
+        // Past the end (e.g. a wrong duration) stays at the end.
+        assert_eq!(knob_at(500), Some(BAR_LEN - 1));
+        assert_eq!(
+            progress_bar(Duration::ZERO, Duration::ZERO).chars().count(),
+            BAR_LEN
+        );
+    }
+}
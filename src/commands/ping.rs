@@ -0,0 +1,58 @@
+//! Implements the `/ping` command.
+//!
+//! The bot responds with its gateway and REST latencies, to tell whether stutter is network-related.
+
+use std::time::Duration;
+use std::time::Instant;
+
+use poise::CreateReply;
+use serenity::CreateEmbed;
+use tracing::instrument;
+
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Show how long the bot takes to reach discord
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 5)]
+pub async fn ping(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    // Only known once the shard has had a heartbeat acknowledged.
+    let shard_id = ctx.serenity_context().shard_id;
+    let gateway = ctx
+        .framework()
+        .shard_manager()
+        .runners
+        .lock()
+        .await
+        .get(&shard_id)
+        .and_then(|runner| runner.latency);
+
+    // Time an edit of the reply, since it's a plain REST request.
+    let reply = ctx.say("Pinging...").await?;
+    let start = Instant::now();
+    reply
+        .edit(ctx, CreateReply::default().content("Pinging..."))
+        .await?;
+    let rest = start.elapsed();
+    tracing::debug!("Gateway latency {gateway:?}, REST round-trip {rest:?}");
+
+    let embed = CreateEmbed::default()
+        .title("Pong!")
+        .field(
+            "Gateway",
+            gateway.map_or("Unknown".to_string(), millis),
+            true,
+        )
+        .field("REST", millis(rest), true);
+    reply
+        .edit(ctx, CreateReply::default().content("").embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Format `duration` in whole milliseconds.
+fn millis(duration: Duration) -> String {
+    format!("{}ms", duration.as_millis())
+}
@@ -4,39 +4,66 @@
 //! In either case, the bot will try to autocomplete the search.
 //!
 
+use std::collections::HashSet;
 use std::str::FromStr;
 use std::time::Duration;
+use std::time::Instant;
 
+use futures::stream;
+use futures::StreamExt;
+use percent_encoding::percent_decode_str;
 use poise::CreateReply;
 use serenity::AutocompleteChoice;
 use serenity::CreateEmbed;
+use serenity::CreateEmbedFooter;
+use songbird::input::codecs::CODEC_REGISTRY;
+use songbird::input::codecs::PROBE;
 use songbird::input::AuxMetadata;
+use songbird::input::HttpRequest;
 use songbird::input::Input;
+use songbird::input::LiveInput;
 use songbird::input::YoutubeDl;
+use symphonia::core::meta::MetadataRevision;
+use symphonia::core::meta::StandardTagKey;
 use tokio::time::sleep;
 use tracing::instrument;
 
+use super::queue;
+use crate::data::missing_title;
 use crate::data::GetData;
 use crate::error::UserError;
 use crate::lib;
 use crate::lib::call;
+use crate::lib::spotify;
 use crate::lib::youtube;
 use crate::serenity;
 use crate::Context;
 use crate::ParakeetError;
 use youtube::SearchResult;
+use youtube::SearchStrategy;
 
 /// Types of queries that are derived from user
 #[derive(Clone, Debug)]
 enum Query {
     /// A fully qualified url to a youtube video
     YoutubeURL(String),
+    /// A fully qualified url to a youtube video that is part of a playlist
+    YoutubePlaylistItem {
+        /// The url to the video itself
+        url: String,
+        /// The playlist id, from the `list` parameter
+        list: String,
+        /// The 1-based position in the playlist, from the `index` parameter
+        index: usize,
+    },
     /// A string query for a youtube search
     YoutubeSearch(String),
-    /// A fully qualified url to something other than youtube, might not work
+    /// A fully qualified url to a soundcloud track
+    SoundCloud(String),
+    /// A spotify link, which is searched for on youtube since spotify can't be played
+    Spotify(url::Url),
+    /// A fully qualified url to something else, might not work
     Other(String),
-    /// Explicitly marked as not supported
-    Unsupported,
 }
 
 impl FromStr for Query {
@@ -47,9 +74,23 @@ impl FromStr for Query {
         if let Ok(url) = s.parse::<url::Url>() {
             // Check the domain
             match url.domain() {
-                Some("www.youtube.com" | "www.youtu.be") => Ok(Query::YoutubeURL(s.to_string())),
-                Some("open.spotify.com") | Some("spotify.com") => Ok(Query::Unsupported),
-                Some(_) | None => Ok(Query::Other(s.to_string())),
+                Some("www.youtube.com" | "www.youtu.be") => match playlist_position(&url) {
+                    Some((list, index)) => Ok(Query::YoutubePlaylistItem {
+                        url: s.to_string(),
+                        list,
+                        index,
+                    }),
+                    None => Ok(Query::YoutubeURL(s.to_string())),
+                },
+                Some("soundcloud.com" | "www.soundcloud.com" | "m.soundcloud.com") => {
+                    Ok(Query::SoundCloud(s.to_string()))
+                }
+                Some("open.spotify.com") | Some("spotify.com") => Ok(Query::Spotify(url)),
+                domain => {
+                    let domain = domain.or(url.host_str()).unwrap_or("<NO HOST>");
+                    tracing::info!("Unknown domain {domain}, trying it anyway");
+                    Ok(Query::Other(s.to_string()))
+                }
             }
         } else {
             // If not url, input is a youtube query
@@ -58,26 +99,214 @@ impl FromStr for Query {
     }
 }
 
+impl Query {
+    /// The name of the platform the query plays from, for logs.
+    fn platform(&self) -> &'static str {
+        match self {
+            Query::YoutubeURL(_) | Query::YoutubePlaylistItem { .. } | Query::YoutubeSearch(_) => {
+                "YouTube"
+            }
+            Query::SoundCloud(_) => "SoundCloud",
+            Query::Spotify(_) => "Spotify",
+            Query::Other(_) => "unknown",
+        }
+    }
+}
+
+/// Find the `list` and `index` parameters of a youtube url, if it has both.
+/// The index is 1-based, so `index=0` is treated as the start of the playlist.
+fn playlist_position(url: &url::Url) -> Option<(String, usize)> {
+    let mut list = None;
+    let mut index = None;
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "list" => list = Some(value.to_string()),
+            "index" => index = value.parse::<usize>().ok().map(|index| index.max(1)),
+            _ => {}
+        }
+    }
+    Some((list?, index?))
+}
+
+/// What to enqueue when a video is part of a playlist.
+enum PlaylistChoice {
+    /// Only the video itself.
+    Single,
+    /// The video and everything after it in the playlist.
+    Rest,
+}
+
+/// How long the "Show queue" button on a `/play` reply keeps working.
+const QUEUE_BUTTON_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How long to wait for the user to pick a [PlaylistChoice].
+const PLAYLIST_CHOICE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Ask the author whether to enqueue only the video or the rest of its playlist.
+/// Defaults to [PlaylistChoice::Single] if the author doesn't answer in time.
+async fn ask_playlist_choice(ctx: &Context<'_>) -> Result<PlaylistChoice, ParakeetError> {
+    let single_id = format!("{}single", ctx.id());
+    let rest_id = format!("{}rest", ctx.id());
+
+    let buttons = vec![
+        serenity::CreateButton::new(&single_id).label("Just this video"),
+        serenity::CreateButton::new(&rest_id).label("Rest of the playlist"),
+    ];
+    let prompt = CreateReply::default()
+        .content("This video is part of a playlist, what should be added?")
+        .components(vec![serenity::CreateActionRow::Buttons(buttons)]);
+    let handle = ctx.send(prompt).await?;
+
+    let interaction = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .custom_ids(vec![single_id, rest_id.clone()])
+        .timeout(PLAYLIST_CHOICE_TIMEOUT)
+        .await;
+
+    let (choice, content) = match interaction {
+        Some(interaction) => {
+            interaction
+                .create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                .await?;
+            if interaction.data.custom_id == rest_id {
+                (PlaylistChoice::Rest, "Adding the rest of the playlist...")
+            } else {
+                (PlaylistChoice::Single, "Adding just this video...")
+            }
+        }
+        None => (
+            PlaylistChoice::Single,
+            "No answer, adding just this video...",
+        ),
+    };
+
+    // Remove the buttons so they can't be pressed again.
+    let answered = CreateReply::default().content(content).components(vec![]);
+    handle.edit(*ctx, answered).await?;
+
+    Ok(choice)
+}
+
+/// How many results the author can pick from with [SearchStrategy::Prompt].
+const SEARCH_CHOICES: u8 = 5;
+
+/// How long to wait for the author to pick a search result.
+const SEARCH_CHOICE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The guild's [SearchStrategy], falling back to the config.
+pub async fn search_strategy(ctx: &Context<'_>) -> Result<SearchStrategy, ParakeetError> {
+    let guild_data = ctx.guild_data().await?;
+    let guild_data = guild_data.lock().await;
+    Ok(guild_data
+        .search_strategy
+        .unwrap_or(ctx.data().config.search_strategy()))
+}
+
+/// Whether playlists added with `/play` are shuffled in the guild, falling back to the config.
+pub async fn auto_shuffle_playlists(ctx: &Context<'_>) -> Result<bool, ParakeetError> {
+    let guild_data = ctx.guild_data().await?;
+    let guild_data = guild_data.lock().await;
+    Ok(guild_data
+        .auto_shuffle_playlists
+        .unwrap_or(ctx.data().config.auto_shuffle_playlists()))
+}
+
+/// Search for `query` and ask the author to pick one of the results.
+/// Returns `None` if the author doesn't answer in time.
+pub async fn ask_search_choice(
+    ctx: &Context<'_>,
+    query: &str,
+) -> Result<Option<SearchResult>, ParakeetError> {
+    // Searching can take longer than discord waits for a response.
+    lib::defer(ctx).await;
+
+    let config = &ctx.data().config;
+    let (backend, ttl) = (config.search_backend(), config.search_cache_ttl());
+    let results = youtube::search_query(query, SEARCH_CHOICES, backend, ttl).await?;
+    if results.is_empty() {
+        Err(UserError::SearchFailed {
+            reason: "No results found".to_string(),
+        })?
+    }
+
+    let menu_id = format!("{}search", ctx.id());
+    let options = results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| serenity::CreateSelectMenuOption::new(&result.name, i.to_string()))
+        .collect();
+    let menu = serenity::CreateSelectMenu::new(
+        &menu_id,
+        serenity::CreateSelectMenuKind::String { options },
+    )
+    .placeholder("Pick a result");
+    let prompt = CreateReply::default()
+        .content(format!("Results for `{query}`:"))
+        .components(vec![serenity::CreateActionRow::SelectMenu(menu)]);
+    let handle = ctx.send(prompt).await?;
+
+    let interaction = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .custom_ids(vec![menu_id])
+        .timeout(SEARCH_CHOICE_TIMEOUT)
+        .await;
+
+    let picked = match interaction {
+        Some(interaction) => {
+            interaction
+                .create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
+                .await?;
+            match &interaction.data.kind {
+                serenity::ComponentInteractionDataKind::StringSelect { values } => values
+                    .first()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .and_then(|i| results.get(i)),
+                _ => None,
+            }
+        }
+        None => None,
+    };
+
+    // Remove the menu so it can't be used again.
+    let content = match picked {
+        Some(result) => format!("Adding `{}`...", result.name),
+        None => "No answer, nothing was added.".to_string(),
+    };
+    let answered = CreateReply::default().content(content).components(vec![]);
+    handle.edit(*ctx, answered).await?;
+
+    Ok(picked.cloned())
+}
+
 /// Autocompletes 'partial' arguments in a play command.
 /// If `input` is a valid url, this will autocomplete into one choice that links to that url
 /// If `input` is a string query, this will autocomplete into multiple choices, each corresponding
 /// to unique youtube search options.
-#[instrument(skip(_ctx))]
-async fn autocomplete_query(_ctx: Context<'_>, input: &str) -> Vec<AutocompleteChoice> {
-    // Don't start until input isn't empty.
-    if input.is_empty() {
+#[instrument(skip(ctx))]
+async fn autocomplete_query(ctx: Context<'_>, input: &str) -> Vec<AutocompleteChoice> {
+    let config = &ctx.data().config;
+    if !config.autocomplete_enabled() {
         return vec![];
+    }
+
+    // Don't search until input isn't empty, but recent tracks can be suggested.
+    if input.is_empty() {
+        if !ctx.data().config.suggest_recent() {
+            return vec![];
+        }
+        return recent_choices(&ctx).await;
     };
 
     // Small delay to prevent unnecessary autocompletions.
-    sleep(Duration::from_millis(600)).await;
+    sleep(config.autocomplete_debounce()).await;
 
     tracing::debug!("Autocompleting for '{input}'");
+    let backend = ctx.data().config.search_backend();
 
     // If input is an url, autocomplete one choice
     if let Ok(url) = url::Url::parse(input) {
-        match youtube::search_link(url).await {
-            Ok(SearchResult { name, url }) => {
+        match youtube::search_link(url, backend).await {
+            Ok(SearchResult { name, url, .. }) => {
                 return vec![AutocompleteChoice::new(name, url)];
             }
             Err(e) => {
@@ -86,11 +315,12 @@ async fn autocomplete_query(_ctx: Context<'_>, input: &str) -> Vec<AutocompleteC
         };
     };
 
-    match youtube::search_query(input, 5).await {
+    let ttl = ctx.data().config.search_cache_ttl();
+    match youtube::search_query(input, 5, backend, ttl).await {
         Ok(results) => {
             return results
                 .into_iter()
-                .map(|SearchResult { name, url }| AutocompleteChoice::new(name, url))
+                .map(|SearchResult { name, url, .. }| AutocompleteChoice::new(name, url))
                 .collect()
         }
         Err(e) => {
@@ -101,55 +331,499 @@ async fn autocomplete_query(_ctx: Context<'_>, input: &str) -> Vec<AutocompleteC
     vec![]
 }
 
+/// How many recently played tracks an empty `/play` box suggests.
+const RECENT_CHOICES: usize = 10;
+
+/// Discord allows autocomplete choice names of at most this many chars.
+const MAX_CHOICE_LEN: usize = 100;
+
+/// Autocompletes the guild's recently played tracks, skipping repeats and tracks without an url.
+async fn recent_choices(ctx: &Context<'_>) -> Vec<AutocompleteChoice> {
+    let Ok(guild_data) = ctx.guild_data().await else {
+        return vec![];
+    };
+    let guild_data = guild_data.lock().await;
+
+    let mut seen = HashSet::new();
+    guild_data
+        .history
+        .iter()
+        .filter_map(|track| {
+            let url = track.url.clone()?;
+            if !seen.insert(url.clone()) {
+                return None;
+            }
+            let title = track.title.clone().unwrap_or(url.clone());
+            let name: String = title.chars().take(MAX_CHOICE_LEN).collect();
+            Some(AutocompleteChoice::new(name, url))
+        })
+        .take(RECENT_CHOICES)
+        .collect()
+}
+
+/// What a [Query] resolved to, see [resolve_query].
+enum Resolved {
+    /// A single track.
+    Track(Box<Source>),
+    /// The rest of the playlist `list`, from the 1-based position `index`.
+    Playlist {
+        /// The playlist id
+        list: String,
+        /// Where to start in the playlist
+        index: usize,
+    },
+    /// A spotify album or playlist.
+    SpotifyCollection(url::Url),
+}
+
+/// A single track that may not have been looked up yet.
+pub struct Source {
+    /// Finds the track and its metadata.
+    ytdl: YoutubeDl,
+    /// Metadata that's already known, e.g. from a search.
+    known: Option<AuxMetadata>,
+    /// Whether `ytdl` searches instead of following an url.
+    is_search: bool,
+    /// See [Query::platform].
+    platform: &'static str,
+}
+
+impl Source {
+    /// The track picked from a search.
+    /// Its metadata is reused if the search found enough to show it, see [play_reply].
+    pub fn from_search(http_client: reqwest::Client, result: SearchResult) -> Self {
+        let known = match (result.title, result.duration) {
+            (Some(title), Some(duration)) => Some(AuxMetadata {
+                title: Some(title),
+                duration: Some(duration),
+                channel: result.channel,
+                thumbnail: result.thumbnail,
+                source_url: Some(result.url.clone()),
+                ..Default::default()
+            }),
+            _ => None,
+        };
+        Self {
+            ytdl: YoutubeDl::new(http_client, result.url),
+            known,
+            is_search: false,
+            platform: "YouTube",
+        }
+    }
+
+    /// Look up the track, returning it as an [Input] along with its metadata.
+    /// Known metadata is returned as is, without spawning the search backend again.
+    pub async fn load(self) -> Result<(Input, AuxMetadata), ParakeetError> {
+        let start = Instant::now();
+        let mut input: Input = self.ytdl.into();
+        if let Some(meta) = self.known {
+            tracing::debug!("Reusing {} metadata from the search", self.platform);
+            return Ok((input, meta));
+        }
+        let meta = match input.aux_metadata().await {
+            Ok(meta) => meta,
+            Err(e) => match youtube::classify(&e.to_string()) {
+                Some(reason) => Err(UserError::VideoUnavailable { reason })?,
+                // Nothing found is a problem with the query, not a bug.
+                None if self.is_search => Err(UserError::SearchFailed {
+                    reason: e.to_string(),
+                })?,
+                None => Err(e)?,
+            },
+        };
+        tracing::debug!(
+            "Resolved {platform} Url: {url} in {elapsed:?}",
+            platform = self.platform,
+            url = meta.source_url.as_deref().unwrap_or("<MISSING URL>"),
+            elapsed = start.elapsed()
+        );
+        Ok((input, meta))
+    }
+}
+
+/// Work out what to play for `query`, asking the author when it's ambiguous.
+/// Videos in a playlist only offer the rest of it if `allow_playlist`.
+/// Returns `None` if the author didn't pick a search result.
+async fn resolve_query(
+    ctx: &Context<'_>,
+    query: Query,
+    allow_playlist: bool,
+) -> Result<Option<Resolved>, ParakeetError> {
+    let http_client = ctx.http_client().await;
+
+    // Searches are left to `YoutubeDl`, which finds the video and its metadata in a single
+    // yt-dlp call instead of searching first and fetching metadata separately.
+    let is_search = matches!(query, Query::YoutubeSearch(_));
+    let platform = query.platform();
+    let ytdl = match query {
+        Query::YoutubeURL(url) | Query::SoundCloud(url) | Query::Other(url) => {
+            YoutubeDl::new(http_client, url)
+        }
+        Query::YoutubePlaylistItem { url, .. } if !allow_playlist => {
+            YoutubeDl::new(http_client, url)
+        }
+        Query::YoutubePlaylistItem { url, list, index } => match ask_playlist_choice(ctx).await? {
+            PlaylistChoice::Single => YoutubeDl::new(http_client, url),
+            PlaylistChoice::Rest => return Ok(Some(Resolved::Playlist { list, index })),
+        },
+        Query::YoutubeSearch(q) => match search_strategy(ctx).await? {
+            SearchStrategy::Auto => YoutubeDl::new_search(http_client, q),
+            SearchStrategy::Prompt => match ask_search_choice(ctx, &q).await? {
+                Some(result) => {
+                    let source = Source::from_search(http_client, result);
+                    return Ok(Some(Resolved::Track(Box::new(source))));
+                }
+                None => return Ok(None),
+            },
+        },
+        Query::Spotify(url) => {
+            // Spotify pages are read before searching, which can take a while.
+            lib::defer(ctx).await;
+            let track = match spotify::Link::of(&url).ok_or(UserError::UnsupportedPlatform)? {
+                spotify::Link::Track => url,
+                spotify::Link::Collection if allow_playlist => {
+                    return Ok(Some(Resolved::SpotifyCollection(url)))
+                }
+                // Only the first track, the rest would push everything back.
+                spotify::Link::Collection => spotify::collection_tracks(&http_client, &url)
+                    .await?
+                    .swap_remove(0),
+            };
+            let config = &ctx.data().config;
+            let (backend, ttl) = (config.search_backend(), config.search_cache_ttl());
+            let result = youtube::resolve_spotify(&http_client, &track, backend, ttl).await?;
+            let source = Source::from_search(http_client, result);
+            return Ok(Some(Resolved::Track(Box::new(source))));
+        }
+    };
+
+    Ok(Some(Resolved::Track(Box::new(Source {
+        ytdl,
+        known: None,
+        is_search,
+        platform,
+    }))))
+}
+
 /// Plays from the given link or does a youtube search on the query.
 #[instrument(skip(ctx))]
-#[poise::command(slash_command, guild_only)]
+#[poise::command(slash_command, prefix_command, guild_only)]
 pub async fn play(
     ctx: Context<'_>,
     #[description = "Youtube query or url"]
     #[autocomplete = "autocomplete_query"]
     query: Query,
+    #[description = "Don't start playing if nothing was, use /resume to start"] paused: Option<
+        bool,
+    >,
 ) -> Result<(), ParakeetError> {
-    // Make a yt-search if we don't have an url
-    let input_url = match query {
-        Query::YoutubeURL(url) | Query::Other(url) => url,
-        Query::YoutubeSearch(q) => {
-            let search_result = youtube::search_best(q).await?;
-            search_result.url
+    let paused = paused == Some(true);
+    let footer = match query {
+        Query::SoundCloud(_) => Some("Playing from SoundCloud"),
+        Query::Spotify(_) => Some("Found on YouTube from a Spotify link"),
+        _ => None,
+    };
+    let source = match resolve_query(&ctx, query, true).await? {
+        Some(Resolved::Track(source)) => *source,
+        Some(Resolved::Playlist { list, index }) => {
+            return play_playlist(ctx, &list, index, paused).await
         }
-        Query::Unsupported => Err(UserError::UnsupportedPlatform)?,
+        Some(Resolved::SpotifyCollection(url)) => return play_spotify(ctx, &url, paused).await,
+        None => return Ok(()),
     };
 
-    tracing::debug!("Resolved Url: {input_url}");
+    // Join the user's call
+    let call = call::join_author(&ctx).await?;
 
+    lib::defer(&ctx).await;
+
+    let (input, meta) = source.load().await?;
+    let handle = call::enqueue_known(&ctx, &call, input, meta.clone()).await?;
+    if paused {
+        call::hold_if_current(&call, &handle).await?;
+    }
+
+    // Build the reply and send it
+    let position = ctx.queue_meta().await?.position(handle.uuid()).await;
+    let mut embed = play_embed(&meta, footer);
+    if let Some(position) = position.filter(|&position| position > 0) {
+        embed = embed.field("Position", position.to_string(), true);
+    }
+    let queue_id = format!("{}queue", ctx.id());
+    let button = |disabled: bool| {
+        vec![serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(&queue_id)
+                .label("Show queue")
+                .disabled(disabled),
+        ])]
+    };
+    let reply = CreateReply::default()
+        .embed(embed.clone())
+        .components(button(false));
+    let reply_handle = ctx.send(reply).await?;
+
+    let anyone = ctx.data().config.queue_button_anyone();
+    while let Some(interaction) = serenity::ComponentInteractionCollector::new(ctx)
+        .custom_ids(vec![queue_id.clone()])
+        .timeout(QUEUE_BUTTON_TIMEOUT)
+        .await
+    {
+        let response = if anyone || interaction.user.id == ctx.author().id {
+            serenity::CreateInteractionResponseMessage::new()
+                .embed(queue::overview_embed(&ctx).await?)
+        } else {
+            let author = ctx.author().id;
+            serenity::CreateInteractionResponseMessage::new().content(format!(
+                "Only <@{author}> can use this, try `/queue` instead."
+            ))
+        };
+        interaction
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::Message(response.ephemeral(true)),
+            )
+            .await?;
+    }
+
+    // Disable the button once it stops working.
+    let finished = CreateReply::default().embed(embed).components(button(true));
+    reply_handle.edit(ctx, finished).await?;
+
+    Ok(())
+}
+
+/// Plays from the given link or search right after the current track.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, rename = "playnext")]
+pub async fn play_next(
+    ctx: Context<'_>,
+    #[description = "Youtube query or url"]
+    #[autocomplete = "autocomplete_query"]
+    query: Query,
+) -> Result<(), ParakeetError> {
+    // Only the one track goes in front, the rest of a playlist would push everything back.
+    let Some(Resolved::Track(source)) = resolve_query(&ctx, query, false).await? else {
+        return Ok(());
+    };
+
+    // Join the user's call
+    let call = call::join_author(&ctx).await?;
+
+    lib::defer(&ctx).await;
+
+    let (input, meta) = source.load().await?;
+    let handle = call::enqueue_front(&ctx, &call, input, meta.clone()).await?;
+
+    // Nothing was playing if it started right away.
+    let position = ctx.queue_meta().await?.position(handle.uuid()).await;
+    let footer = position
+        .is_some_and(|position| position > 0)
+        .then_some("Playing next");
+    ctx.send(play_reply(&meta, footer)).await?;
+
+    Ok(())
+}
+
+/// Enqueues the playlist `list` starting from the 1-based position `start`.
+/// If `paused`, the first track waits for a resume if nothing was playing.
+async fn play_playlist(
+    ctx: Context<'_>,
+    list: &str,
+    start: usize,
+    paused: bool,
+) -> Result<(), ParakeetError> {
+    // Join the user's call
+    let call = call::join_author(&ctx).await?;
+
+    lib::defer(&ctx).await;
+
+    let entries = youtube::playlist_from(list, start, ctx.data().config.search_backend()).await?;
+    enqueue_batch(ctx, &call, entries, 0, "the playlist", paused).await
+}
+
+/// Enqueues the tracks of the spotify album or playlist at `url`, each found with a youtube search.
+/// At most [spotify::COLLECTION_LIMIT] tracks are added.
+/// If `paused`, the first track waits for a resume if nothing was playing.
+async fn play_spotify(ctx: Context<'_>, url: &url::Url, paused: bool) -> Result<(), ParakeetError> {
     let http_client = ctx.http_client().await;
 
     // Join the user's call
     let call = call::join_author(&ctx).await?;
 
-    ctx.defer().await?;
+    lib::defer(&ctx).await;
+
+    let tracks = spotify::collection_tracks(&http_client, url).await?;
+    let config = &ctx.data().config;
+    let (backend, ttl) = (config.search_backend(), config.search_cache_ttl());
+
+    // Each track is a page fetch and a search, so they're found concurrently but kept in order.
+    let http_client = &http_client;
+    let found: Vec<_> = stream::iter(tracks)
+        .map(|track| async move {
+            let result = youtube::resolve_spotify(http_client, &track, backend, ttl).await;
+            (track, result)
+        })
+        .buffered(call::METADATA_LOOKUPS)
+        .collect()
+        .await;
+
+    // Tracks that can't be found on youtube shouldn't stop the rest from being added.
+    let mut entries = Vec::with_capacity(found.len());
+    let mut not_found = 0;
+    for (track, result) in found {
+        match result {
+            Ok(result) => entries.push(result),
+            Err(e) => {
+                tracing::debug!("Skipping spotify track {track}: {e}");
+                not_found += 1;
+            }
+        }
+    }
+    let source = "Spotify, found on YouTube";
+    enqueue_batch(ctx, &call, entries, not_found, source, paused).await
+}
 
-    // Get input and it's metadata.
-    let mut input: Input = YoutubeDl::new(http_client, input_url.clone()).into();
-    let meta = input.aux_metadata().await?;
+/// Enqueues a batch of `entries` from `source` (e.g. "the playlist"), then shuffles them if
+/// [configured](auto_shuffle_playlists) and replies with a summary.
+/// `unavailable` entries that couldn't be found beforehand are counted as skipped.
+///
+/// The first entry that loads is shown in the reply, so the reply only waits for it,
+/// the rest are looked up concurrently with [call::enqueue_all].
+/// If `paused`, the first track waits for a resume if nothing was playing.
+async fn enqueue_batch(
+    ctx: Context<'_>,
+    call: &call::CallRef,
+    entries: Vec<SearchResult>,
+    unavailable: usize,
+    source: &str,
+    paused: bool,
+) -> Result<(), ParakeetError> {
+    let http_client = ctx.http_client().await;
+    let queue_meta = ctx.queue_meta().await?;
 
-    let _handle = call::enqueue(&ctx, &call, input).await?;
+    // A few dead videos shouldn't stop the rest from being added.
+    let mut entries = entries.into_iter();
+    let mut skipped = unavailable;
+    // Only what gets added now is shuffled, by id since the queue may change meanwhile.
+    let mut added_ids = HashSet::new();
+
+    let mut first_meta = None;
+    let mut full_at = None;
+    for SearchResult { name, url, .. } in entries.by_ref() {
+        let mut input: Input = YoutubeDl::new(http_client.clone(), url).into();
+        let meta = match input.aux_metadata().await {
+            Ok(meta) => meta,
+            Err(e) => {
+                tracing::debug!("Skipping '{name}' from {source}: {e}");
+                skipped += 1;
+                continue;
+            }
+        };
+        match call::enqueue_known(&ctx, call, input, meta.clone()).await {
+            Ok(handle) => {
+                added_ids.insert(handle.uuid());
+                if paused {
+                    call::hold_if_current(call, &handle).await?;
+                }
+                first_meta = Some(meta);
+                break;
+            }
+            // A full queue isn't the entry's fault, and nothing after it fits either.
+            Err(ParakeetError::UserError(UserError::QueueFull { limit })) => {
+                full_at = Some(limit);
+                break;
+            }
+            Err(e) => {
+                tracing::debug!("Skipping '{name}' from {source}: {e}");
+                skipped += 1;
+            }
+        }
+    }
+    let mut reply = match first_meta {
+        Some(meta) => play_reply(&meta, None),
+        None => CreateReply::default(),
+    };
+    let progress = if added_ids.is_empty() {
+        None
+    } else {
+        let adding = reply
+            .clone()
+            .content(format!("Adding the rest of {source}..."));
+        Some(ctx.send(adding).await?)
+    };
+
+    // The rest is looked up concurrently, but still queued in order.
+    let (names, inputs): (Vec<_>, Vec<_>) = entries
+        .map(|SearchResult { name, url, .. }| {
+            let input: Input = YoutubeDl::new(http_client.clone(), url).into();
+            (name, (input, None))
+        })
+        .unzip();
+    let enqueued = match full_at {
+        // The entry that didn't fit is counted too.
+        Some(limit) => call::Enqueued {
+            added: Vec::new(),
+            failed: Vec::new(),
+            overflow: inputs.len() + 1,
+            limit,
+        },
+        None => call::enqueue_all(&ctx, call, inputs).await?,
+    };
+    for (num, e) in &enqueued.failed {
+        tracing::debug!("Skipping '{}' from {source}: {e}", names[*num]);
+    }
+    added_ids.extend(enqueued.added.iter().map(|handle| handle.uuid()));
+    skipped += enqueued.failed.len();
 
     // Build the reply and send it
-    let reply = play_reply(&meta);
-    ctx.send(reply).await?;
+    let shuffled = if auto_shuffle_playlists(&ctx).await? {
+        // A track that started playing right away stays where it is.
+        call::shuffle_ids(call, &queue_meta, &added_ids).await? > 0
+    } else {
+        false
+    };
+
+    let mut content = format!("Added {} tracks from {source}", added_ids.len());
+    content.push_str(if shuffled { " (shuffled)." } else { "." });
+    if skipped > 0 {
+        content.push_str(&format!(" Skipped {skipped} unavailable."));
+    }
+    if let Some(note) = enqueued.overflow_note() {
+        content.push_str(&format!("\n{note}"));
+    }
+    reply = reply.content(content);
+    match progress {
+        // Replace the progress message, if it can still be edited.
+        Some(progress) => {
+            if let Err(e) = progress.edit(ctx, reply.clone()).await {
+                tracing::debug!("Couldn't edit the {source} progress message: {e}");
+                lib::send_late(&ctx, reply).await?;
+            }
+        }
+        None => lib::send_late(&ctx, reply).await?,
+    }
 
     Ok(())
 }
 
 /// Plays from the given link or does a youtube search on the query.
 #[instrument(skip(ctx))]
-#[poise::command(slash_command, guild_only, rename = "playfile")]
+#[poise::command(slash_command, prefix_command, guild_only, rename = "playfile")]
 pub async fn play_file(
     ctx: Context<'_>,
     #[description = "Attachment or file."] file: serenity::Attachment,
 ) -> Result<(), ParakeetError> {
+    if !is_playable_type(file.content_type.as_deref()) {
+        Err(UserError::UnsupportedFile {
+            content_type: file.content_type.unwrap_or_default(),
+        })?
+    }
+
     let input_url = file.url;
+    // Discord may keep the filename url-encoded.
+    let filename = percent_decode_str(&file.filename)
+        .decode_utf8_lossy()
+        .into_owned();
 
     tracing::debug!("Url: {input_url}");
 
@@ -158,24 +832,103 @@ pub async fn play_file(
     // Join the user's call
     let call = call::join_author(&ctx).await?;
 
-    // Get input and it's metadata.
-    let mut input: Input = YoutubeDl::new(http_client, input_url.clone()).into();
-    let meta = input.aux_metadata().await?;
+    // The file is already hosted by discord, so it's streamed directly instead of through yt-dlp.
+    // Its tags are read by parsing the start of it, which also gets it ready to play.
+    let input: Input = HttpRequest::new(http_client.clone(), input_url.clone()).into();
+    let (input, tags) = match input.make_playable_async(&CODEC_REGISTRY, &PROBE).await {
+        Ok(mut input) => {
+            let tags = file_tags(&mut input);
+            (input, tags)
+        }
+        Err(e) => {
+            // Left for songbird to try again once it plays, with just the filename.
+            tracing::warn!("Failed to read the tags of {filename}: {e}");
+            let input = HttpRequest::new(http_client, input_url.clone()).into();
+            (input, AuxMetadata::default())
+        }
+    };
+    let meta = file_metadata(tags, filename, input_url);
 
-    let _handle = call::enqueue(&ctx, &call, input).await?;
+    // Known metadata keeps the url, so the track can be re-created (e.g. for loops or /replay).
+    let _handle = call::enqueue_known(&ctx, &call, input, meta.clone()).await?;
 
     // Build the reply and send it
-    let reply = play_reply(&meta);
+    let reply = play_reply(&meta, None);
     ctx.send(reply).await?;
 
     Ok(())
 }
 
-/// Create a reply based on the metadata of the input.
-fn play_reply(meta: &AuxMetadata) -> CreateReply {
-    let title = meta.title.clone().unwrap_or("<MISSING TITLE>".to_string());
+/// Metadata for a file at `url` with the given `tags`, using `filename` if it has no title.
+fn file_metadata(tags: AuxMetadata, filename: String, url: String) -> AuxMetadata {
+    AuxMetadata {
+        title: tags.title.or(Some(filename)),
+        source_url: Some(url),
+        ..tags
+    }
+}
+
+/// The title, artist and duration of a parsed file `input`, as far as its tags have them.
+/// Nothing is found if it wasn't parsed yet.
+fn file_tags(input: &mut Input) -> AuxMetadata {
+    let mut meta = AuxMetadata::default();
+    if let Input::Live(LiveInput::Parsed(parsed), _) = input {
+        let track = parsed
+            .format
+            .tracks()
+            .iter()
+            .find(|track| track.id == parsed.track_id);
+        let params = track.map(|track| &track.codec_params);
+        if let Some((time_base, frames)) = params.and_then(|p| p.time_base.zip(p.n_frames)) {
+            let time = time_base.calc_time(frames);
+            meta.duration =
+                Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac));
+        }
+
+        // Tags can be in the container (e.g. vorbis comments) or in front of it (e.g. ID3).
+        let mut read_tags = |revision: &MetadataRevision| {
+            for tag in revision.tags() {
+                // RIFF tags keep their C string terminator.
+                let value = tag.value.to_string();
+                let value = value.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+                if value.is_empty() {
+                    continue;
+                }
+                let value = value.to_string();
+                match tag.std_key {
+                    Some(StandardTagKey::TrackTitle) => {
+                        meta.title = meta.title.take().or(Some(value))
+                    }
+                    Some(StandardTagKey::Artist) => {
+                        meta.channel = meta.channel.take().or(Some(value))
+                    }
+                    _ => {}
+                }
+            }
+        };
+        if let Some(revision) = parsed.format.metadata().current() {
+            read_tags(revision);
+        }
+        if let Some(revision) = parsed.meta.get().as_ref().and_then(|meta| meta.current()) {
+            read_tags(revision);
+        }
+    }
+    meta
+}
+
+/// Create a reply based on the metadata of the input, with an optional `footer`.
+pub fn play_reply(meta: &AuxMetadata, footer: Option<&str>) -> CreateReply {
+    CreateReply::default().embed(play_embed(meta, footer))
+}
 
-    let mut embed = CreateEmbed::default().title(title);
+/// The embed of a [play_reply].
+fn play_embed(meta: &AuxMetadata, footer: Option<&str>) -> CreateEmbed {
+    let title = meta
+        .title
+        .clone()
+        .unwrap_or_else(|| missing_title().to_string());
+
+    let mut embed = CreateEmbed::default().title(lib::truncate_title(&title, lib::MAX_TITLE_LEN));
 
     // Make title link to url if available.
     if let Some(url) = meta.source_url.clone() {
@@ -196,6 +949,130 @@ fn play_reply(meta: &AuxMetadata) -> CreateReply {
     if let Some(channel) = meta.channel.clone() {
         embed = embed.field("Channel", channel, true);
     }
+    if let Some(footer) = footer {
+        embed = embed.footer(CreateEmbedFooter::new(footer));
+    }
+
+    embed
+}
+
+/// Whether a file with the given content type can be played, i.e. it's audio or video.
+/// Files without a content type are given a chance.
+fn is_playable_type(content_type: Option<&str>) -> bool {
+    let Some(content_type) = content_type else {
+        return true;
+    };
+    // Ignore parameters, like in `audio/ogg; codecs=opus`.
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.starts_with("audio/") || mime.starts_with("video/") || mime == "application/ogg"
+}
+
+#[cfg(test)]
+mod tests {
+    use songbird::tracks::Track;
+
+    use super::*;
+    use crate::data::LoopMode;
+    use crate::data::TrackMetadata;
+
+    #[test]
+    fn finds_playlist_positions() {
+        let position = |url: &str| playlist_position(&url::Url::parse(url).unwrap());
+        assert_eq!(
+            position("https://www.youtube.com/watch?v=abc&list=PL1&index=4"),
+            Some(("PL1".to_string(), 4))
+        );
+        assert_eq!(
+            position("https://www.youtube.com/watch?v=abc&list=PL1"),
+            None
+        );
+        assert_eq!(
+            position("https://www.youtube.com/watch?v=abc&list=PL1&index=0"),
+            Some(("PL1".to_string(), 1))
+        );
+    }
+
+    /// A short silent wav file, titled `title` in its INFO chunk.
+    fn tagged_wav(title: &str) -> Vec<u8> {
+        let mut name = title.as_bytes().to_vec();
+        name.push(0);
+        let name_len = name.len() as u32;
+        if name.len() % 2 == 1 {
+            name.push(0);
+        }
+        let samples = vec![0u8; 1600];
+
+        let mut info = b"INFO".to_vec();
+        info.extend(b"INAM");
+        info.extend(name_len.to_le_bytes());
+        info.extend(name);
+
+        let mut body = b"WAVE".to_vec();
+        body.extend(b"fmt ");
+        body.extend(16u32.to_le_bytes());
+        // PCM, mono, 8 kHz, 16 bit.
+        body.extend(1u16.to_le_bytes());
+        body.extend(1u16.to_le_bytes());
+        body.extend(8000u32.to_le_bytes());
+        body.extend(16000u32.to_le_bytes());
+        body.extend(2u16.to_le_bytes());
+        body.extend(16u16.to_le_bytes());
+        body.extend(b"LIST");
+        body.extend((info.len() as u32).to_le_bytes());
+        body.extend(info);
+        body.extend(b"data");
+        body.extend((samples.len() as u32).to_le_bytes());
+        body.extend(samples);
+
+        let mut wav = b"RIFF".to_vec();
+        wav.extend((body.len() as u32).to_le_bytes());
+        wav.extend(body);
+        wav
+    }
+
+    #[tokio::test]
+    async fn file_tags_are_read() {
+        let input = Input::from(tagged_wav("Tagged Song"));
+        let mut input = input
+            .make_playable_async(&CODEC_REGISTRY, &PROBE)
+            .await
+            .unwrap();
+        let tags = file_tags(&mut input);
+        assert_eq!(tags.title.as_deref(), Some("Tagged Song"));
+        assert_eq!(tags.duration, Some(Duration::from_millis(100)));
+
+        let meta = file_metadata(tags, "song.wav".to_string(), "https://a.b/c".to_string());
+        assert_eq!(meta.title.as_deref(), Some("Tagged Song"));
+    }
+
+    #[test]
+    fn files_keep_their_url() {
+        let url = "https://cdn.discordapp.com/attachments/1/2/song.mp3";
+        let track = Track::new(Input::from(&[0u8; 64][..]));
+        let meta = file_metadata(
+            AuxMetadata::default(),
+            "song.mp3".to_string(),
+            url.to_string(),
+        );
+        assert_eq!(meta.title.as_deref(), Some("song.mp3"));
+        let meta = TrackMetadata::from_aux(&track, meta);
+        assert_eq!(meta.url.as_deref(), Some(url));
+
+        // Tracks without an url would be dropped here.
+        let rebuilt = call::rebuild_tracks(reqwest::Client::new(), 1.0, LoopMode::Off, vec![meta]);
+        assert_eq!(rebuilt.len(), 1);
+        assert_eq!(rebuilt[0].1.url.as_deref(), Some(url));
+    }
 
-    CreateReply::default().embed(embed)
+    #[test]
+    fn only_audio_and_video_files_play() {
+        assert!(is_playable_type(Some("audio/mpeg")));
+        assert!(is_playable_type(Some("audio/ogg; codecs=opus")));
+        assert!(is_playable_type(Some("video/mp4")));
+        assert!(is_playable_type(Some("application/ogg")));
+        assert!(is_playable_type(None));
+        assert!(!is_playable_type(Some("image/png")));
+        assert!(!is_playable_type(Some("application/pdf")));
+        assert!(!is_playable_type(Some("text/plain; charset=utf-8")));
+    }
 }
@@ -0,0 +1,92 @@
+//! Implements the `/playall` command.
+//!
+//! The bot searches youtube and adds the top results to the queue in one go.
+//! Results that fail to load are skipped instead of stopping the whole batch.
+
+use poise::CreateReply;
+use songbird::input::Input;
+use songbird::input::YoutubeDl;
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::lib::call;
+use crate::lib::youtube;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Add the top search results for a query to the queue.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 10,
+    rename = "playall"
+)]
+pub async fn play_all(
+    ctx: Context<'_>,
+    #[description = "Youtube query"] query: String,
+    #[description = "How many results to add"]
+    #[min = 1]
+    count: Option<u8>,
+) -> Result<(), ParakeetError> {
+    let config = &ctx.data().config;
+    let queue_meta = ctx.queue_meta().await?;
+
+    // Don't search for more than the queue can hold.
+    let limit = call::max_queue_len(&ctx).await?;
+    let room = limit.saturating_sub(queue_meta.len().await);
+    if room == 0 {
+        Err(UserError::QueueFull { limit })?
+    }
+    let count = count
+        .unwrap_or(config.max_batch())
+        .min(config.max_batch())
+        .min(room.try_into().unwrap_or(u8::MAX));
+
+    let http_client = ctx.http_client().await;
+
+    // Join the user's call
+    let call = call::join_author(&ctx).await?;
+    lib::defer(&ctx).await;
+
+    let results = youtube::search_query(
+        &query,
+        count,
+        config.search_backend(),
+        config.search_cache_ttl(),
+    )
+    .await?;
+    if results.is_empty() {
+        Err(UserError::SearchFailed {
+            reason: "No results found".to_string(),
+        })?
+    }
+
+    let (names, inputs): (Vec<_>, Vec<_>) = results
+        .into_iter()
+        .map(|result| {
+            let input: Input = YoutubeDl::new(http_client.clone(), result.url).into();
+            (result.name, (input, None))
+        })
+        .unzip();
+
+    let enqueued = call::enqueue_all(&ctx, &call, inputs).await?;
+    for (num, e) in &enqueued.failed {
+        tracing::debug!("Skipping result {num} '{}': {e}", names[*num]);
+    }
+
+    let mut reply = format!("Added {} tracks for `{query}`.", enqueued.added.len());
+    if !enqueued.failed.is_empty() {
+        let skipped = enqueued.failed.len();
+        reply.push_str(&format!(" Skipped {skipped} that couldn't be loaded."));
+    }
+    if let Some(note) = enqueued.overflow_note() {
+        reply.push_str(&format!("\n{note}"));
+    }
+    lib::send_late(&ctx, CreateReply::default().content(reply)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Implements the `/preview` command.
+//!
+//! The bot plays the first few seconds of a track in the queue.
+//! Songbird only has one call per guild, so if something is playing it is paused
+//! during the preview and resumed once the preview is over.
+
+use songbird::input::Input;
+use songbird::input::YoutubeDl;
+use songbird::tracks::PlayMode;
+use tracing::instrument;
+
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Play the first few seconds of a track in the queue.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 5)]
+pub async fn preview(
+    ctx: Context<'_>,
+    #[description = "Position in the queue"] index: usize,
+) -> Result<(), ParakeetError> {
+    let queue_meta = ctx.queue_meta().await?;
+    let track = queue_meta.get(index).await.ok_or(UserError::BadArgs {
+        input: Some(index.to_string()),
+    })?;
+    let title = track.title.unwrap_or_else(|| missing_title().to_string());
+    let Some(url) = track.url else {
+        ctx.reply(format!("`{title}` can't be previewed.")).await?;
+        return Ok(());
+    };
+
+    let http_client = ctx.http_client().await;
+    let duration = ctx.data().config.preview_duration();
+
+    // Join the user's call
+    let call = lib::call::join_author(&ctx).await?;
+
+    // Pause the queue, but only if it's actually playing, so it's not resumed by mistake.
+    let current = { call.lock().await.queue().current() };
+    let paused = match current {
+        Some(handle) if handle.get_info().await?.playing == PlayMode::Play => {
+            handle.pause()?;
+            Some(handle)
+        }
+        _ => None,
+    };
+
+    let input: Input = YoutubeDl::new(http_client, url).into();
+    let preview = lib::call::play_transient(&call, input).await;
+
+    ctx.reply(format!(
+        "Previewing `{title}` for {}",
+        lib::format_duration(&duration)
+    ))
+    .await?;
+
+    let task = tokio::spawn(async move {
+        // Only start timing once the preview can actually be heard.
+        if let Err(e) = preview.make_playable_async().await {
+            tracing::warn!("Preview failed to start: {e}");
+        }
+        tokio::time::sleep(duration).await;
+
+        // The preview may have ended already, that's fine.
+        let _ = preview.stop();
+        if let Some(handle) = paused {
+            if let Err(e) = handle.play() {
+                tracing::warn!("Failed to resume after preview: {e}");
+            }
+        }
+    });
+    // Nothing is left to resume if the bot disconnects first.
+    ctx.guild_data()
+        .await?
+        .lock()
+        .await
+        .tasks
+        .track("preview", task);
+
+    Ok(())
+}
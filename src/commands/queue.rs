@@ -1,46 +1,234 @@
 //! Implements the `/queue` command.
 //!
-//! The bot responds with an embed displaying all the songs in the queue.
+//! The bot responds with an embed displaying all the songs in the queue, a page at a time,
+//! or with an m3u playlist file of the queue.
+
+use std::time::Duration;
 
 use poise::CreateReply;
+use serenity::CreateAttachment;
 use serenity::CreateEmbed;
+use serenity::CreateEmbedFooter;
 use tracing::instrument;
 
 use crate::data::GetData;
+use crate::data::QueueMeta;
+use crate::data::QueuePages;
 use crate::data::TrackMetadata;
 use crate::error::UserError;
+use crate::lib;
+use crate::lib::eta;
+use crate::lib::m3u;
 use crate::serenity;
 use crate::Context;
 use crate::ParakeetError;
 
+/// How many lines each page of the queue has.
+const TRACKS_PER_PAGE: usize = 10;
+
+/// Discord allows embed descriptions of at most this many chars.
+const MAX_DESCRIPTION_LEN: usize = 4096;
+
+/// How long the page buttons keep working after they were last used.
+const PAGE_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Show what's coming up
 #[instrument]
-#[poise::command(slash_command, guild_only, guild_cooldown = 2)]
-pub async fn queue(ctx: Context<'_>) -> Result<(), ParakeetError> {
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn queue(
+    ctx: Context<'_>,
+    #[description = "Only show tracks you added"] mine: Option<bool>,
+    #[description = "Only show tracks this user added"] user: Option<serenity::User>,
+    #[description = "Send the queue as an m3u playlist file"] export: Option<bool>,
+    #[description = "Show repeats in a row once"] collapse: Option<bool>,
+    #[description = "Sum up how much each person added"] by_requester: Option<bool>,
+) -> Result<(), ParakeetError> {
     let guild = ctx.guild().ok_or(UserError::NotInGuild)?.name.clone();
 
-    let queue_meta = {
-        let guild_data = ctx.guild_data().await?;
-        let lock = guild_data.lock().await;
-        lock.queue_metadata.clone()
+    let queue_meta = ctx.live_queue_meta().await?;
+
+    // An explicit user takes priority over `mine`.
+    let requester = match (user, mine) {
+        (Some(user), _) => Some(user.id),
+        (None, Some(true)) => Some(ctx.author().id),
+        _ => None,
     };
+    let is_shown = |track: &TrackMetadata| requester.is_none() || track.requested_by == requester;
 
-    let mut embed = CreateEmbed::default()
-        .description(queue_meta.display_string().await)
-        .title(format!("{guild} Queue"));
+    if export == Some(true) {
+        let tracks = queue_meta.snapshot().await;
+        let content = m3u::to_m3u(tracks.iter().filter(|track| is_shown(track)));
+        let file = CreateAttachment::bytes(content, "queue.m3u8");
+        ctx.send(CreateReply::default().attachment(file)).await?;
+        return Ok(());
+    }
 
-    // Add thumbnail if front has a thumbnail.
-    if let Some(TrackMetadata {
-        thumbnail_url: Some(url),
-        ..
-    }) = queue_meta.front().await
-    {
-        embed = embed.thumbnail(url)
+    let pages = queue_meta
+        .pages(is_shown, collapse == Some(true), TRACKS_PER_PAGE)
+        .await;
+
+    let embed = base_embed(&guild, &queue_meta).await;
+
+    let header = match by_requester {
+        Some(true) => requester_header(&queue_meta.snapshot().await),
+        _ => String::new(),
     };
 
-    let reply = CreateReply::default().embed(embed);
+    // How long until the whole queue is done, only for the unfiltered queue.
+    let mut elapsed = lib::call::current_position(&ctx).await?;
+    let footer = match requester {
+        None => eta_footer(&ctx, &queue_meta, elapsed).await?,
+        Some(_) => String::new(),
+    };
+
+    // A single page doesn't need buttons.
+    if pages.len() == 1 {
+        let embed = page_embed(embed, &pages, 0, elapsed, &header, &footer);
+        ctx.send(CreateReply::default().embed(embed)).await?;
+        return Ok(());
+    }
+
+    let prev_id = format!("{}prev", ctx.id());
+    let next_id = format!("{}next", ctx.id());
+    let buttons = |disabled: bool| {
+        vec![serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(&prev_id)
+                .label("Previous")
+                .disabled(disabled),
+            serenity::CreateButton::new(&next_id)
+                .label("Next")
+                .disabled(disabled),
+        ])]
+    };
+
+    let mut page = 0;
+    let reply = CreateReply::default()
+        .embed(page_embed(
+            embed.clone(),
+            &pages,
+            page,
+            elapsed,
+            &header,
+            &footer,
+        ))
+        .components(buttons(false));
+    let handle = ctx.send(reply).await?;
+
+    // Pages are only rendered when they're asked for.
+    while let Some(interaction) = serenity::ComponentInteractionCollector::new(ctx)
+        .custom_ids(vec![prev_id.clone(), next_id.clone()])
+        .timeout(PAGE_TIMEOUT)
+        .await
+    {
+        page = if interaction.data.custom_id == next_id {
+            (page + 1) % pages.len()
+        } else {
+            (page + pages.len() - 1) % pages.len()
+        };
+        elapsed = lib::call::current_position(&ctx).await?;
+        let update = serenity::CreateInteractionResponseMessage::new().embed(page_embed(
+            embed.clone(),
+            &pages,
+            page,
+            elapsed,
+            &header,
+            &footer,
+        ));
+        interaction
+            .create_response(
+                ctx,
+                serenity::CreateInteractionResponse::UpdateMessage(update),
+            )
+            .await?;
+    }
 
-    ctx.send(reply).await?;
+    // Disable the buttons once nobody is paging anymore.
+    let finished = CreateReply::default()
+        .embed(page_embed(embed, &pages, page, elapsed, &header, &footer))
+        .components(buttons(true));
+    handle.edit(ctx, finished).await?;
 
     Ok(())
 }
+
+/// The first page of the whole queue, like a plain `/queue` shows.
+pub async fn overview_embed(ctx: &Context<'_>) -> Result<CreateEmbed, ParakeetError> {
+    let guild = ctx.guild().ok_or(UserError::NotInGuild)?.name.clone();
+    let queue_meta = ctx.live_queue_meta().await?;
+    let pages = queue_meta.pages(|_| true, false, TRACKS_PER_PAGE).await;
+    let elapsed = lib::call::current_position(ctx).await?;
+    let footer = eta_footer(ctx, &queue_meta, elapsed).await?;
+
+    let embed = base_embed(&guild, &queue_meta).await;
+    Ok(page_embed(embed, &pages, 0, elapsed, "", &footer))
+}
+
+/// The queue embed without any tracks yet, with the current track's thumbnail if it has one.
+async fn base_embed(guild: &str, queue_meta: &QueueMeta) -> CreateEmbed {
+    let embed = CreateEmbed::default().title(format!("{guild} Queue"));
+    match queue_meta.front().await {
+        Some(TrackMetadata {
+            thumbnail_url: Some(url),
+            ..
+        }) => embed.thumbnail(url),
+        _ => embed,
+    }
+}
+
+/// How long until the whole queue is done, or nothing if it's empty.
+async fn eta_footer(
+    ctx: &Context<'_>,
+    queue_meta: &QueueMeta,
+    elapsed: Duration,
+) -> Result<String, ParakeetError> {
+    if queue_meta.is_empty().await {
+        return Ok(String::new());
+    }
+    let tracks = queue_meta.snapshot().await;
+    let loop_mode = ctx.guild_data().await?.lock().await.loop_mode;
+    let eta = eta::until_empty(&tracks, elapsed, loop_mode);
+    Ok(format!("Time left: {eta}"))
+}
+
+/// Fill `embed` with the 0-based `page` of `pages`, noting which page it is in the footer.
+/// `elapsed` is how far along the current track is, and `header` goes above the tracks.
+fn page_embed(
+    embed: CreateEmbed,
+    pages: &QueuePages,
+    page: usize,
+    elapsed: Duration,
+    header: &str,
+    footer: &str,
+) -> CreateEmbed {
+    let page_info = format!("Page {}/{}", page + 1, pages.len());
+    let tracks = pages.render(page, Some(elapsed));
+    // The header is dropped if it would push the page over the embed limit.
+    let description = if header.is_empty() || header.len() + tracks.len() > MAX_DESCRIPTION_LEN {
+        tracks
+    } else {
+        format!("{header}\n{tracks}")
+    };
+    let footer = match footer {
+        "" => page_info,
+        footer => format!("{footer} | {page_info}"),
+    };
+    embed
+        .description(description)
+        .footer(CreateEmbedFooter::new(footer))
+}
+
+/// A line per requester of `tracks`, with how many tracks they added and for how long.
+fn requester_header(tracks: &[TrackMetadata]) -> String {
+    TrackMetadata::per_requester(tracks)
+        .into_iter()
+        .map(|(user, count, duration)| {
+            let user = match user {
+                Some(user) => format!("<@{user}>"),
+                None => "Unknown".to_string(),
+            };
+            let s = if count == 1 { "" } else { "s" };
+            let duration = lib::format_duration(&duration);
+            format!("{user}: {count} track{s} {duration}\n")
+        })
+        .collect()
+}
@@ -0,0 +1,52 @@
+//! Implements the `/queuefit` command.
+//!
+//! The bot removes tracks from the end of the queue until what's left finishes within
+//! a time budget, e.g. to wrap up by the end of an event.
+//! Tracks with an unknown duration might not fit, so they're removed along with
+//! everything after them.
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::data::TrackMetadata;
+use crate::lib;
+use crate::lib::eta;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Trim the queue to fit in a time budget.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 5,
+    rename = "queuefit"
+)]
+pub async fn queue_fit(
+    ctx: Context<'_>,
+    #[description = "How long the queue can last, like 1:00:00"] budget: String,
+) -> Result<(), ParakeetError> {
+    let budget = lib::parse_duration(&budget)?;
+
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    let tracks = queue_meta.snapshot().await;
+    let elapsed = lib::call::current_position(&ctx).await?;
+    let keep = eta::fit_len(&tracks, elapsed, budget);
+    let removed = lib::call::truncate(&call, &queue_meta, keep).await?;
+
+    let remaining = queue_meta.snapshot().await;
+    let total = TrackMetadata::total_duration(&remaining).saturating_sub(elapsed);
+    let total = lib::format_duration(&total);
+    tracing::info!("Removed {} tracks to fit the queue", removed.len());
+    ctx.reply(format!(
+        "Removed {} tracks, the queue now lasts {total}.",
+        removed.len()
+    ))
+    .await?;
+
+    Ok(())
+}
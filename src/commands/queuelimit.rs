@@ -0,0 +1,45 @@
+//! Implements the `/queuelimit` command.
+//!
+//! Lets server admins change how many tracks their queue can hold,
+//! overriding the default from the config. The override is kept across restarts if
+//! [persistence](crate::Config::persistence_dir) is enabled, otherwise it lasts until the bot restarts.
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Set how many tracks the queue can hold. Leave empty to use the default.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 2,
+    required_permissions = "MANAGE_GUILD",
+    rename = "queuelimit"
+)]
+pub async fn queue_limit(
+    ctx: Context<'_>,
+    #[description = "Max tracks in the queue"]
+    #[min = 1]
+    limit: Option<usize>,
+) -> Result<(), ParakeetError> {
+    {
+        let guild_data = ctx.guild_data().await?;
+        guild_data.lock().await.max_queue_len = limit;
+    }
+
+    let limit = lib::call::max_queue_len(&ctx).await?;
+    tracing::info!("Queue limit set to {limit}");
+    let lasts = match ctx.data().config.persistence_dir() {
+        Some(_) => "It's kept across restarts.",
+        None => "It lasts until I restart.",
+    };
+    ctx.reply(format!("Queue limit set to {limit} tracks. {lasts}"))
+        .await?;
+
+    Ok(())
+}
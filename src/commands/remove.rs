@@ -0,0 +1,81 @@
+//! Implements the `/remove` command.
+//!
+//! Tracks are picked through autocomplete, which refers to them by their
+//! [id](crate::data::TrackMetadata::id) so the right track is removed even if the
+//! queue shifts in the meantime. A plain queue position is also accepted.
+
+use serenity::AutocompleteChoice;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Discord allows at most this many autocomplete choices.
+const MAX_CHOICES: usize = 25;
+
+/// Discord allows autocomplete choice names of at most this many chars.
+const MAX_CHOICE_LEN: usize = 100;
+
+/// Autocompletes queued tracks whose title contains `input`.
+async fn autocomplete_track(ctx: Context<'_>, input: &str) -> Vec<AutocompleteChoice> {
+    let Ok(queue_meta) = ctx.queue_meta().await else {
+        return vec![];
+    };
+    let input = input.to_lowercase();
+
+    queue_meta
+        .snapshot()
+        .await
+        .into_iter()
+        .enumerate()
+        .filter_map(|(num, track)| {
+            let title = track.title.unwrap_or_else(|| missing_title().to_string());
+            if !title.to_lowercase().contains(&input) {
+                return None;
+            }
+            let name: String = format!("{num}. {title}")
+                .chars()
+                .take(MAX_CHOICE_LEN)
+                .collect();
+            Some(AutocompleteChoice::new(name, track.id.to_string()))
+        })
+        .take(MAX_CHOICES)
+        .collect()
+}
+
+/// Remove a track from the queue.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Track to remove"]
+    #[autocomplete = "autocomplete_track"]
+    track: String,
+) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    // Autocompleted choices are ids, but a typed queue position works too.
+    let id = match (Uuid::parse_str(&track), track.parse::<usize>()) {
+        (Ok(id), _) => Some(id),
+        (_, Ok(index)) => queue_meta.get(index).await.map(|meta| meta.id),
+        _ => None,
+    }
+    .ok_or(UserError::BadArgs {
+        input: Some(track.clone()),
+    })?;
+
+    let removed = lib::call::remove(&call, &queue_meta, id).await?;
+    let title = removed.title.unwrap_or_else(|| missing_title().to_string());
+    tracing::info!("Removing {title}");
+    ctx.reply(format!("Removed `{title}`")).await?;
+
+    Ok(())
+}
@@ -0,0 +1,34 @@
+//! Implements the `/remove-dupes` command.
+//!
+//! Removes every track that was queued before, keeping the first of each.
+//! Unlike plain `/dedupe`, repeats anywhere in the queue are removed, not just ones in a row.
+//! Same as `/dedupe all:true`.
+
+use tracing::instrument;
+
+use super::dedupe;
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Remove tracks queued more than once, keeping the first one.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 5,
+    rename = "remove-dupes"
+)]
+pub async fn remove_dupes(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    let removed = lib::call::remove_duplicates(&call, &queue_meta).await?;
+    tracing::info!("Removed {removed} duplicate tracks");
+    ctx.reply(dedupe::removed_reply(removed)).await?;
+
+    Ok(())
+}
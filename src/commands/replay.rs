@@ -0,0 +1,41 @@
+//! Implements the `/replay` command.
+//!
+//! The bot adds the last finished track back to the queue, right after the current track.
+
+use tracing::instrument;
+
+use super::play;
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Play the last finished track again, next
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn replay(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let last = {
+        let guild_data = ctx.guild_data().await?;
+        let guild_data = guild_data.lock().await;
+        guild_data.history.front().cloned()
+    };
+    let last = last.ok_or(UserError::NoHistory)?;
+    // Tracks without an url can't be re-created.
+    let url = last.url.clone().ok_or(UserError::NoHistory)?;
+    let meta = last.to_aux();
+
+    let call = lib::call::join_author(&ctx).await?;
+    let input = lib::call::rebuild_input(ctx.http_client().await, url);
+    lib::call::enqueue_front(&ctx, &call, input, meta.clone()).await?;
+
+    let title = meta
+        .title
+        .clone()
+        .unwrap_or_else(|| missing_title().to_string());
+    tracing::info!("Replaying {title}");
+    ctx.send(play::play_reply(&meta, Some("Replaying"))).await?;
+
+    Ok(())
+}
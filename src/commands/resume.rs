@@ -0,0 +1,28 @@
+//! Implements the `/resume` command.
+//!
+//! The bot resumes the current track, e.g. one added with `/play paused:true`.
+
+use tracing::instrument;
+
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Resumes the current audio track.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn resume(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    let resumed = lib::call::resume(&call, &queue_meta).await?;
+    ctx.guild_data().await?.lock().await.mark_progress();
+    let title = resumed.title.unwrap_or_else(|| missing_title().to_string());
+    tracing::info!("Resuming {title}");
+    ctx.reply(format!("Resuming `{title}`")).await?;
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+//! Implements the `/search` command.
+//!
+//! The bot searches youtube for the query and lets the author pick one of the top results
+//! from a menu, which is then added to the queue.
+
+use tracing::instrument;
+
+use super::play;
+use crate::data::GetData;
+use crate::lib;
+use crate::lib::call;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Search youtube and pick a result to play
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "What to search for"] query: String,
+) -> Result<(), ParakeetError> {
+    // Only the author's pick counts, and the menu is removed once it times out.
+    let Some(result) = play::ask_search_choice(&ctx, &query).await? else {
+        return Ok(());
+    };
+
+    let call = call::join_author(&ctx).await?;
+    lib::defer(&ctx).await;
+
+    let source = play::Source::from_search(ctx.http_client().await, result);
+    let (input, meta) = source.load().await?;
+    call::enqueue_known(&ctx, &call, input, meta.clone()).await?;
+    ctx.send(play::play_reply(&meta, None)).await?;
+
+    Ok(())
+}
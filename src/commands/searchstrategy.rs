@@ -0,0 +1,44 @@
+//! Implements the `/searchstrategy` command.
+//!
+//! Lets server admins choose whether `/play` plays the top search result or lets
+//! the user pick one, overriding the default from the config.
+//! The override only lasts until the bot restarts.
+
+use tracing::instrument;
+
+use super::play;
+use crate::data::GetData;
+use crate::lib::youtube::SearchStrategy;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Set how `/play` handles searches. Leave empty to use the default.
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 2,
+    required_permissions = "MANAGE_GUILD",
+    rename = "searchstrategy"
+)]
+pub async fn search_strategy(
+    ctx: Context<'_>,
+    #[description = "Play the top result, or pick from the results"] strategy: Option<
+        SearchStrategy,
+    >,
+) -> Result<(), ParakeetError> {
+    {
+        let guild_data = ctx.guild_data().await?;
+        guild_data.lock().await.search_strategy = strategy;
+    }
+
+    let reply = match play::search_strategy(&ctx).await? {
+        SearchStrategy::Auto => "Searches now play the top result.",
+        SearchStrategy::Prompt => "Searches now let you pick from the results.",
+    };
+    tracing::info!("{reply}");
+    ctx.reply(reply).await?;
+
+    Ok(())
+}
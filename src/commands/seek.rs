@@ -0,0 +1,32 @@
+//! Implements the `/seek` command.
+//!
+//! The bot jumps to a timestamp in the current track, given as `hh:mm:ss`, `mm:ss`, or seconds.
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Jump to a point in the current track.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn seek(
+    ctx: Context<'_>,
+    #[description = "Where to jump to, like 1:30"] timestamp: String,
+) -> Result<(), ParakeetError> {
+    let position = lib::parse_duration(&timestamp)?;
+
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+    lib::defer(&ctx).await;
+
+    let position = lib::call::seek(&call, &queue_meta, position).await?;
+    let position = lib::format_duration(&position);
+    tracing::info!("Seeked to {position}");
+    ctx.reply(format!("Jumped to {position}.")).await?;
+
+    Ok(())
+}
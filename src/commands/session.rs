@@ -0,0 +1,31 @@
+//! Implements the `/session` command.
+//!
+//! The bot responds with stats about what it played since it joined the call.
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Show how much has been played this session.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn session(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let (tracks, time) = {
+        let guild_data = ctx.guild_data().await?;
+        let guild_data = guild_data.lock().await;
+        let session = &guild_data.session;
+        (session.tracks_played, session.time_played)
+    };
+
+    let s = if tracks == 1 { "" } else { "s" };
+    ctx.reply(format!(
+        "This session: {tracks} track{s}, {} played.",
+        lib::format_duration(&time)
+    ))
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+//! Implements the `/shuffle` command.
+//!
+//! The bot shuffles every track after the current one.
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Shuffle the upcoming tracks.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn shuffle(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    let shuffled = lib::call::shuffle(&call, &queue_meta).await?;
+    if shuffled == 0 {
+        ctx.reply("Nothing to shuffle.").await?;
+        return Ok(());
+    }
+
+    tracing::info!("Shuffled {shuffled} tracks");
+    ctx.reply(format!("Shuffled {shuffled} tracks.")).await?;
+
+    Ok(())
+}
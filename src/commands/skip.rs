@@ -1,40 +1,46 @@
 //! Implements the `/skip` command.
 //!
-//! The bot will skip the current track and start playing the next one
-//! in the queue (if there is one).
+//! The bot will skip the current track, and optionally a few after it,
+//! and start playing the next one in the queue (if there is one).
 
 use tracing::instrument;
 
+use crate::data::missing_title;
 use crate::data::GetData;
-use crate::error::UserError;
+use crate::data::TrackMetadata;
 use crate::lib;
 use crate::Context;
 use crate::ParakeetError;
 
 /// Skips the current audio track.
 #[instrument(skip(ctx))]
-#[poise::command(slash_command, guild_only, guild_cooldown = 2)]
-pub async fn skip(ctx: Context<'_>) -> Result<(), ParakeetError> {
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn skip(
+    ctx: Context<'_>,
+    #[description = "How many tracks to skip, including the current one"]
+    #[min = 1]
+    count: Option<usize>,
+) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
     let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
 
-    let call = call.lock().await;
+    let (skipped, next) = lib::call::skip(&call, &queue_meta, count.unwrap_or(1)).await?;
+    ctx.guild_data().await?.lock().await.mark_progress();
 
-    let queue = call.queue();
-    match queue.current() {
-        None => Err(UserError::EmptyQueue)?,
-        Some(handle) => {
-            let meta = {
-                let guild_data = ctx.guild_data().await?;
-                let queue = guild_data.lock().await;
-                queue.queue_metadata.clone()
-            };
-            let current_meta = meta.front().await.ok_or(UserError::EmptyQueue)?;
-            let title = current_meta.title.unwrap_or("<MISSING_TITLE>".to_string());
-            tracing::info!("Skipping {title}");
-            handle.stop()?;
-            ctx.reply(format!("Skipping `{title}`")).await?;
-        }
+    let title = |meta: &TrackMetadata| match &meta.title {
+        Some(title) => lib::truncate_title(title, lib::MAX_TITLE_LEN),
+        None => missing_title().to_string(),
+    };
+    let mut content = match skipped.as_slice() {
+        [skipped] => format!("Skipping `{}`", title(skipped)),
+        skipped => format!("Skipping {} tracks", skipped.len()),
+    };
+    tracing::info!("{content}");
+    if let Some(next) = next {
+        content.push_str(&format!(", up next is `{}`", title(&next)));
     }
+    ctx.reply(content).await?;
 
     Ok(())
 }
@@ -0,0 +1,41 @@
+//! Implements the `/skipto` command.
+//!
+//! The bot skips every track before a queue position, so the track there plays next.
+//! Positions are the ones shown by `/queue`, where the current track is 0.
+
+use tracing::instrument;
+
+use crate::data::missing_title;
+use crate::data::GetData;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Jump ahead to a track in the queue
+#[instrument(skip(ctx))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    guild_cooldown = 2,
+    rename = "skipto"
+)]
+pub async fn skip_to(
+    ctx: Context<'_>,
+    #[description = "Queue position to jump to"]
+    #[min = 1]
+    position: usize,
+) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
+    let call = lib::call::get_call(&ctx).await?;
+    let queue_meta = ctx.queue_meta().await?;
+
+    let target = lib::call::skip_to(&call, &queue_meta, position).await?;
+    ctx.guild_data().await?.lock().await.mark_progress();
+
+    let title = target.title.unwrap_or_else(|| missing_title().to_string());
+    tracing::info!("Skipping to {title}");
+    ctx.reply(format!("Skipping to `{title}`")).await?;
+
+    Ok(())
+}
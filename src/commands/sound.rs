@@ -0,0 +1,92 @@
+//! Implements the `/sound` command.
+//!
+//! The bot plays a short clip from the configured sounds directory, where each file's
+//! name (without its extension) is the name of the sound.
+//! Sounds are mixed on top of whatever is playing instead of interrupting it,
+//! and aren't part of the queue.
+
+use std::path::PathBuf;
+
+use serenity::AutocompleteChoice;
+use songbird::input::File;
+use songbird::input::Input;
+use tracing::instrument;
+
+use crate::error::UserError;
+use crate::lib;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Discord allows at most this many autocomplete choices.
+const MAX_CHOICES: usize = 25;
+
+/// The sounds in `dir`, sorted by name.
+async fn list_sounds(dir: &str) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut sounds = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if !entry.file_type().await?.is_file() {
+            continue;
+        }
+        if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+            sounds.push((name.to_string(), path.clone()));
+        }
+    }
+    sounds.sort();
+    Ok(sounds)
+}
+
+/// Autocompletes the names of sounds containing `input`.
+async fn autocomplete_sound(ctx: Context<'_>, input: &str) -> Vec<AutocompleteChoice> {
+    let Some(dir) = ctx.data().config.sounds_dir() else {
+        return vec![];
+    };
+    let input = input.to_lowercase();
+    match list_sounds(dir).await {
+        Ok(sounds) => sounds
+            .into_iter()
+            .filter(|(name, _)| name.to_lowercase().contains(&input))
+            .take(MAX_CHOICES)
+            .map(|(name, _)| AutocompleteChoice::new(name.clone(), name))
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to list sounds in {dir}: {e}");
+            vec![]
+        }
+    }
+}
+
+/// Play a short sound clip.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn sound(
+    ctx: Context<'_>,
+    #[description = "Name of the sound"]
+    #[autocomplete = "autocomplete_sound"]
+    name: String,
+) -> Result<(), ParakeetError> {
+    let Some(dir) = ctx.data().config.sounds_dir() else {
+        ctx.reply("Sounds aren't enabled.").await?;
+        return Ok(());
+    };
+
+    let sounds = list_sounds(dir).await?;
+    let (_, path) = sounds
+        .into_iter()
+        .find(|(sound, _)| sound.eq_ignore_ascii_case(&name))
+        .ok_or(UserError::BadArgs {
+            input: Some(name.clone()),
+        })?;
+
+    // Join the user's call
+    let call = lib::call::join_author(&ctx).await?;
+
+    let input: Input = File::new(path).into();
+    lib::call::play_transient(&call, input).await;
+
+    ctx.reply(format!("Playing `{name}`")).await?;
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Implements the `/status` command.
+//!
+//! The bot responds with an embed summarizing its state in the guild:
+//! which voice channel it's in, what's playing, the queue, the volume, and the loop mode.
+
+use poise::ChoiceParameter;
+use poise::CreateReply;
+use serenity::CreateEmbed;
+use songbird::tracks::PlayMode;
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// How many tracks are listed under "Up next", including the current (marked) one.
+const UP_NEXT_LEN: usize = 5;
+
+/// Discord allows embed field values of at most this many chars.
+const MAX_FIELD_LEN: usize = 1024;
+
+/// Show what the bot is up to
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn status(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let guild_id = ctx.guild_id().ok_or(UserError::GuildOnly)?;
+
+    // Only look at an existing call, checking the status shouldn't join anything.
+    let (channel, current) = match lib::call::get_manager(&ctx).await?.get(guild_id) {
+        Some(call) => {
+            let call = call.lock().await;
+            (call.current_channel(), call.queue().current())
+        }
+        None => (None, None),
+    };
+
+    let Some(channel) = channel else {
+        let embed = CreateEmbed::default()
+            .title("Status")
+            .description("Idle, not in a voice channel.");
+        ctx.send(CreateReply::default().embed(embed)).await?;
+        return Ok(());
+    };
+
+    // The current track may end while checking, in which case nothing is playing.
+    let state = match current {
+        Some(handle) => match handle.get_info().await.map(|info| info.playing) {
+            Ok(PlayMode::Play) => "Playing",
+            Ok(PlayMode::Pause) => "Paused",
+            _ => "Stopped",
+        },
+        None => "Nothing playing",
+    };
+
+    let queue_meta = ctx.queue_meta().await?;
+    let queued = queue_meta.len().await;
+    let (up_next, _) = queue_meta.display_page(0, UP_NEXT_LEN).await;
+    // Fields are shorter than descriptions, so drop lines that don't fit.
+    let mut up_next_field = String::new();
+    for line in up_next.lines() {
+        if up_next_field.len() + line.len() + 1 > MAX_FIELD_LEN {
+            break;
+        }
+        up_next_field.push_str(line);
+        up_next_field.push('\n');
+    }
+
+    let (volume, loop_mode) = {
+        let guild_data = ctx.guild_data().await?;
+        let guild_data = guild_data.lock().await;
+        (guild_data.volume, guild_data.loop_mode)
+    };
+
+    let embed = CreateEmbed::default()
+        .title("Status")
+        .field("Channel", format!("<#{}>", channel.0), true)
+        .field("State", state, true)
+        .field("Queue", format!("{queued} tracks"), true)
+        .field("Volume", format!("{:.0}%", volume * 100.0), true)
+        .field("Loop", loop_mode.name(), true)
+        .field("Up next", up_next_field, false);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
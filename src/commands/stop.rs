@@ -5,20 +5,27 @@
 
 use tracing::instrument;
 
+use crate::data::GetData;
 use crate::lib;
 use crate::Context;
 use crate::ParakeetError;
 
 /// Stop the bot, delete the queue, and leave the call.
 #[instrument]
-#[poise::command(slash_command, guild_only)]
+#[poise::command(slash_command, prefix_command, guild_only)]
 pub async fn stop(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    lib::call::require_same_channel(&ctx)?;
     let call = lib::call::get_call(&ctx).await?;
     let mut call = call.lock().await;
 
     tracing::info!("Stopping the queue.");
     call.queue().stop();
     call.leave().await?;
+
+    // Disconnecting resets this too, but only once the event is handled.
+    let guild_data = ctx.guild_data().await?;
+    guild_data.lock().await.session = Default::default();
+
     ctx.reply("Queue deleted.").await?;
     Ok(())
 }
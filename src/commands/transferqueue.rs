@@ -0,0 +1,112 @@
+//! Implements the `/transferqueue` command.
+//!
+//! Moves the queue of one guild to another, for operators running the bot in several servers.
+//! Tracks are re-created from their urls in the target guild, so tracks without one are dropped,
+//! as are tracks that don't fit in the target's queue.
+
+use serenity::GuildId;
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::lib::call::Enqueued;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Move the queue of one server to another.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, owners_only, rename = "transferqueue")]
+pub async fn transfer_queue(
+    ctx: Context<'_>,
+    #[description = "Server id to take the queue from"] from_guild: String,
+    #[description = "Server id to move the queue to"] to_guild: String,
+) -> Result<(), ParakeetError> {
+    // Ids are too large for discord's integer options, so they're parsed here.
+    let parse = |id: &str| {
+        id.trim()
+            .parse::<GuildId>()
+            .map_err(|_| UserError::BadArgs {
+                input: Some(id.to_string()),
+            })
+    };
+    let (from, to) = (parse(&from_guild)?, parse(&to_guild)?);
+    if from == to {
+        Err(UserError::BadArgs {
+            input: Some(to_guild.clone()),
+        })?
+    }
+
+    let manager = lib::call::get_manager(&ctx).await?;
+    let from_call = manager.get(from);
+    let to_call = manager.get(to).ok_or(UserError::NoActiveCall)?;
+
+    let data = ctx.data();
+    let from_meta = data
+        .guild_data_for(from)
+        .await
+        .lock()
+        .await
+        .queue_metadata
+        .clone();
+    // The tracks are set up with the target's settings, like /play there would.
+    let (to_meta, volume, loop_mode, limit) = {
+        let to_data = data.guild_data_for(to).await;
+        let mut to_data = to_data.lock().await;
+        to_data.mark_progress();
+        (
+            to_data.queue_metadata.clone(),
+            to_data.volume,
+            to_data.loop_mode,
+            to_data.max_queue_len.unwrap_or(data.config.max_queue_len()),
+        )
+    };
+
+    // Empty out the source, ending whatever it was playing.
+    let tracks = match from_call {
+        Some(call) => {
+            let call = call.lock().await;
+            let tracks = from_meta.snapshot().await;
+            call.queue().stop();
+            from_meta.clear().await;
+            tracks
+        }
+        None => {
+            let tracks = from_meta.snapshot().await;
+            from_meta.clear().await;
+            tracks
+        }
+    };
+
+    let http_client = ctx.http_client().await;
+    let tracks = lib::call::rebuild_tracks(http_client, volume, loop_mode, tracks);
+    let mut enqueued = Enqueued {
+        added: Vec::with_capacity(tracks.len()),
+        failed: Vec::new(),
+        overflow: 0,
+        limit,
+    };
+    for (track, meta) in tracks {
+        // Checked for each track, in case something else is queued meanwhile.
+        if to_meta.len().await >= limit {
+            enqueued.overflow += 1;
+            continue;
+        }
+        let handle = lib::call::enqueue_with_meta(&to_call, &to_meta, track, meta).await;
+        enqueued.added.push(handle);
+    }
+
+    let transferred = enqueued.added.len();
+    tracing::info!(
+        "Transferred {transferred} tracks from {from} to {to}, {} didn't fit",
+        enqueued.overflow
+    );
+    let mut content = format!("Transferred {transferred} tracks.");
+    if let Some(note) = enqueued.overflow_note() {
+        content.push_str(&format!("\n{note}"));
+    }
+    ctx.reply(content).await?;
+
+    Ok(())
+}
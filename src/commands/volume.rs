@@ -0,0 +1,61 @@
+//! Implements the `/volume` command.
+//!
+//! By default the volume is stored per guild, so it applies to the tracks already in the
+//! queue as well as those added later. It can also be set for just the current track.
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::error::UserError;
+use crate::lib;
+use crate::Context;
+use crate::ParakeetError;
+
+/// The loudest volume allowed, in percent.
+const MAX_VOLUME: u32 = 200;
+
+/// What a volume change applies to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+enum VolumeScope {
+    /// The guild's volume, used by every track.
+    #[default]
+    Guild,
+    /// Only the current track, the next one goes back to the guild's volume.
+    Track,
+}
+
+/// Set the playback volume.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn volume(
+    ctx: Context<'_>,
+    #[description = "Volume in percent, from 0 to 200"] percent: u32,
+    #[description = "Whole server or current track"] scope: Option<VolumeScope>,
+) -> Result<(), ParakeetError> {
+    if percent > MAX_VOLUME {
+        Err(UserError::BadArgs {
+            input: Some(percent.to_string()),
+        })?
+    }
+
+    lib::call::require_same_channel(&ctx)?;
+    let volume = percent as f32 / 100.0;
+    let call = lib::call::get_call(&ctx).await?;
+
+    let reply = match scope.unwrap_or_default() {
+        VolumeScope::Guild => {
+            ctx.guild_data().await?.lock().await.volume = volume;
+            lib::call::set_volume(&call, volume).await?;
+            tracing::info!("Volume set to {percent}%");
+            format!("Volume set to {percent}% for this server.")
+        }
+        VolumeScope::Track => {
+            lib::call::set_current_volume(&call, volume).await?;
+            tracing::info!("Current track volume set to {percent}%");
+            format!("Volume set to {percent}% for the current track only.")
+        }
+    };
+    ctx.reply(reply).await?;
+
+    Ok(())
+}
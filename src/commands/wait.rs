@@ -0,0 +1,31 @@
+//! Implements the `/wait` command.
+//!
+//! The bot estimates when the author's next track plays, from the durations of
+//! the tracks ahead of it, how far along the current track is, and the [LoopMode](crate::data::LoopMode).
+
+use tracing::instrument;
+
+use crate::data::GetData;
+use crate::lib;
+use crate::lib::eta;
+use crate::lib::eta::Eta;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Show when your next track plays.
+#[instrument(skip(ctx))]
+#[poise::command(slash_command, prefix_command, guild_only, guild_cooldown = 2)]
+pub async fn wait(ctx: Context<'_>) -> Result<(), ParakeetError> {
+    let tracks = ctx.queue_meta().await?.snapshot().await;
+    let loop_mode = ctx.guild_data().await?.lock().await.loop_mode;
+    let elapsed = lib::call::current_position(&ctx).await?;
+
+    let reply = match eta::until_next(&tracks, elapsed, loop_mode, ctx.author().id) {
+        None => "You don't have any tracks queued.".to_string(),
+        Some(Eta::Now) => "Your track is playing right now!".to_string(),
+        Some(eta) => format!("Your next track plays in {eta}"),
+    };
+    ctx.reply(reply).await?;
+
+    Ok(())
+}
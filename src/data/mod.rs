@@ -1,48 +1,263 @@
 //! This module contains everything relating to [Data].
 
+pub mod persist;
 mod queue_metadata;
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 
 use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
 use reqwest::Client;
 use serenity::GuildId;
 use serenity::UserId;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 
 use crate::error::UserError;
+use crate::lib::youtube::SearchStrategy;
+use crate::log::LogHandle;
+use crate::log::NotifyHistory;
 use crate::serenity;
+use crate::Config;
 use crate::Context;
+pub use queue_metadata::consecutive_duplicates;
+pub use queue_metadata::missing_title;
+pub use queue_metadata::set_missing_title;
 pub use queue_metadata::QueueMeta;
+pub use queue_metadata::QueuePages;
 pub use queue_metadata::TrackMetadata;
 
+/// How many finished tracks a guild's [history](GuildData::history) keeps.
+const HISTORY_LEN: usize = 25;
+
 /// Convenience type alias for [UserData]
 type UserDataRef = Arc<Mutex<UserData>>;
 
 /// Convenience type alias for [GuildData]
-type GuildDataRef = Arc<Mutex<GuildData>>;
+pub type GuildDataRef = Arc<Mutex<GuildData>>;
 
 /// The data kept between shards
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Data {
+    /// Settings read at startup
+    pub config: Config,
     /// List of users to send bug notifications
     pub notify_list: HashSet<UserId>,
     /// Per-User data
     pub user_data: Mutex<HashMap<UserId, UserDataRef>>,
-    /// Per-Guild data
-    pub guild_data: Mutex<HashMap<GuildId, GuildDataRef>>,
+    /// Per-Guild data, in an [Arc] so it can be saved on shutdown, see [persist].
+    pub guild_data: Arc<Mutex<HashMap<GuildId, GuildDataRef>>>,
+    /// Changes the log level at runtime, if tracing was installed.
+    pub log_handle: Option<LogHandle>,
+    /// When each running command started, keyed by [poise::Context::id].
+    /// Only tracked if [Config::command_timing] is enabled.
+    pub command_starts: Mutex<HashMap<u64, Instant>>,
+    /// When commands were last re-registered after going stale.
+    pub last_reregister: Mutex<Option<Instant>>,
+    /// When the bot finished starting up, used for its uptime.
+    pub started: Instant,
+    /// Recent bug notifications, so repeated errors aren't sent over and over.
+    pub notify_history: Mutex<NotifyHistory>,
+}
+
+impl Default for Data {
+    fn default() -> Self {
+        Self {
+            config: Default::default(),
+            notify_list: Default::default(),
+            user_data: Default::default(),
+            guild_data: Default::default(),
+            log_handle: None,
+            command_starts: Default::default(),
+            last_reregister: Default::default(),
+            started: Instant::now(),
+            notify_history: Default::default(),
+        }
+    }
+}
+
+impl Data {
+    /// Returns a reference to the [GuildData] of `guild`, creating it if needed.
+    /// New guilds start at the [configured volume](Config::default_volume).
+    pub async fn guild_data_for(&self, guild: GuildId) -> GuildDataRef {
+        let mut map = self.guild_data.lock().await;
+        map.entry(guild)
+            .or_insert_with(|| Arc::new(Mutex::new(GuildData::new(&self.config))))
+            .clone()
+    }
 }
 
+/// Data stored on a per-user basis.
 #[derive(Debug, Default)]
 pub struct UserData {}
 
 /// Data stored on a per=guild basis.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct GuildData {
     /// Metadata of tracks in queue, uses an [Arc] internally
     pub queue_metadata: QueueMeta,
+    /// Stats since the bot last joined a call.
+    pub session: SessionStats,
+    /// Overrides [Config::max_queue_len] for this guild.
+    pub max_queue_len: Option<usize>,
+    /// What gets repeated once a track ends.
+    pub loop_mode: LoopMode,
+    /// Overrides [Config::search_strategy] for this guild.
+    pub search_strategy: Option<SearchStrategy>,
+    /// Overrides [Config::auto_shuffle_playlists] for this guild.
+    pub auto_shuffle_playlists: Option<bool>,
+    /// Volume new tracks start at, where `1.0` is unchanged.
+    pub volume: f32,
+    /// Tracks that finished playing, most recent first.
+    /// Unlike [session](GuildData::session), this is kept across disconnects.
+    pub history: VecDeque<TrackMetadata>,
+    /// Background tasks that only make sense while the bot is in a call.
+    pub tasks: GuildTasks,
+    /// The queue was [loaded from disk](persist) and should play once the bot joins.
+    pub restored_queue: bool,
+    /// The queue is left over from a disconnect or restart and nothing in it is playing,
+    /// until the bot joins again and resumes or clears it.
+    pub stale_queue: bool,
+    /// The text channel tracks were last added from, where started tracks are announced.
+    pub announce_channel: Option<serenity::ChannelId>,
+    /// When playback last moved along, e.g. a track was added, resumed or skipped.
+    /// Used to leave calls that stay paused for too long, see [Config::paused_timeout].
+    pub last_progress: Instant,
+}
+
+impl Default for GuildData {
+    fn default() -> Self {
+        Self {
+            queue_metadata: Default::default(),
+            session: Default::default(),
+            max_queue_len: None,
+            loop_mode: Default::default(),
+            search_strategy: None,
+            auto_shuffle_playlists: None,
+            volume: 1.0,
+            history: VecDeque::new(),
+            tasks: Default::default(),
+            restored_queue: false,
+            stale_queue: false,
+            announce_channel: None,
+            last_progress: Instant::now(),
+        }
+    }
+}
+
+impl GuildData {
+    /// Default [GuildData], but with the settings from `config` applied.
+    pub fn new(config: &Config) -> Self {
+        Self {
+            volume: config.default_volume(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds a finished track to the [history](GuildData::history), forgetting the oldest
+    /// one if it's full.
+    pub fn record_played(&mut self, track: TrackMetadata) {
+        self.history.push_front(track);
+        self.history.truncate(HISTORY_LEN);
+    }
+
+    /// The queue as far as read-only commands are concerned, empty while it's
+    /// [stale](GuildData::stale_queue).
+    pub fn live_queue(&self) -> QueueMeta {
+        if self.stale_queue {
+            QueueMeta::default()
+        } else {
+            self.queue_metadata.clone()
+        }
+    }
+
+    /// Note that playback just moved along, restarting the [paused timeout](Config::paused_timeout).
+    pub fn mark_progress(&mut self) {
+        self.last_progress = Instant::now();
+    }
+
+    /// Note that the bot just joined a call. Time spent outside of a call isn't counted
+    /// towards the [paused timeout](Config::paused_timeout).
+    pub fn joined(&mut self) {
+        self.mark_progress();
+    }
+
+    /// Whether playback hasn't moved along for at least `timeout`.
+    pub fn paused_for(&self, timeout: Duration) -> bool {
+        self.last_progress.elapsed() >= timeout
+    }
+}
+
+/// Spawned tasks belonging to a guild, so they can be aborted once they're no longer needed.
+/// Anything still running is aborted when this is dropped.
+#[derive(Debug, Default)]
+pub struct GuildTasks {
+    /// Each task with a short description for logging.
+    handles: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+impl GuildTasks {
+    /// Keep track of a spawned task, forgetting any that already finished.
+    pub fn track(&mut self, name: &'static str, handle: JoinHandle<()>) {
+        self.handles.retain(|(_, handle)| !handle.is_finished());
+        self.handles.push((name, handle));
+    }
+
+    /// Abort every task that's still running, returning their names.
+    pub fn abort_all(&mut self) -> Vec<&'static str> {
+        self.handles
+            .drain(..)
+            .filter(|(_, handle)| !handle.is_finished())
+            .map(|(name, handle)| {
+                handle.abort();
+                name
+            })
+            .collect()
+    }
+}
+
+impl Drop for GuildTasks {
+    fn drop(&mut self) {
+        for (_, handle) in &self.handles {
+            handle.abort();
+        }
+    }
+}
+
+/// What gets repeated once a track ends.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum LoopMode {
+    /// Nothing repeats.
+    #[default]
+    Off,
+    /// The current track repeats forever.
+    Track,
+    /// Finished tracks go to the back of the queue.
+    Queue,
+}
+
+impl LoopMode {
+    /// The mode after this one, going off -> track -> queue -> off.
+    pub fn next(self) -> LoopMode {
+        match self {
+            LoopMode::Off => LoopMode::Track,
+            LoopMode::Track => LoopMode::Queue,
+            LoopMode::Queue => LoopMode::Off,
+        }
+    }
+}
+
+/// Playback stats for the current session, reset on disconnect.
+#[derive(Debug, Default)]
+pub struct SessionStats {
+    /// How many tracks finished playing.
+    pub tracks_played: usize,
+    /// Total time spent actually playing tracks.
+    pub time_played: Duration,
 }
 
 /// Key to store a [Client] in a [TypeMapKey]
@@ -51,6 +266,13 @@ impl serenity::prelude::TypeMapKey for HttpKey {
     type Value = Client;
 }
 
+/// Key to store [Data::guild_data] in a [TypeMapKey],
+/// so it can still be reached on shutdown, outside of the framework.
+pub struct GuildDataKey;
+impl serenity::prelude::TypeMapKey for GuildDataKey {
+    type Value = Arc<Mutex<HashMap<GuildId, GuildDataRef>>>;
+}
+
 /// Is able to get an [UserData] and [Client].
 pub trait GetData {
     #[allow(dead_code)]
@@ -60,6 +282,18 @@ pub trait GetData {
     async fn http_client(&self) -> Client;
     /// Returns a reference to [GuildData]. Errors if not in a guild.
     async fn guild_data(&self) -> Result<GuildDataRef, UserError>;
+    /// Returns the guild's [QueueMeta], without holding on to the [GuildData] lock.
+    async fn queue_meta(&self) -> Result<QueueMeta, UserError> {
+        let guild_data = self.guild_data().await?;
+        let guild_data = guild_data.lock().await;
+        Ok(guild_data.queue_metadata.clone())
+    }
+    /// Like [queue_meta](GetData::queue_meta), but see [GuildData::live_queue].
+    async fn live_queue_meta(&self) -> Result<QueueMeta, UserError> {
+        let guild_data = self.guild_data().await?;
+        let guild_data = guild_data.lock().await;
+        Ok(guild_data.live_queue())
+    }
 }
 
 impl GetData for Context<'_> {
@@ -90,15 +324,47 @@ impl GetData for Context<'_> {
 
     async fn guild_data(&self) -> Result<GuildDataRef, UserError> {
         let guild = self.guild_id().ok_or(UserError::GuildOnly)?;
-        let mut map = self.data().guild_data.lock().await;
+        Ok(self.data().guild_data_for(guild).await)
+    }
+}
 
-        match map.get(&guild) {
-            Some(data) => Ok(data.clone()),
-            None => {
-                let default_data: GuildDataRef = Default::default();
-                map.insert(guild, default_data.clone());
-                Ok(default_data)
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joining_restarts_paused_timeout() {
+        let timeout = Duration::from_secs(60);
+        let mut guild_data = GuildData {
+            last_progress: Instant::now() - Duration::from_secs(3 * 60 * 60),
+            ..Default::default()
+        };
+        assert!(guild_data.paused_for(timeout));
+
+        guild_data.joined();
+        assert!(!guild_data.paused_for(timeout));
+    }
+
+    #[tokio::test]
+    async fn stale_queue_reads_as_empty() {
+        let track = TrackMetadata {
+            id: uuid::Uuid::nil(),
+            title: None,
+            duration: None,
+            channel: None,
+            thumbnail_url: None,
+            url: None,
+            requested_by: None,
+        };
+        let mut guild_data = GuildData {
+            queue_metadata: QueueMeta::restore(vec![track]),
+            stale_queue: true,
+            ..Default::default()
+        };
+        assert!(guild_data.live_queue().is_empty().await);
+        assert_eq!(guild_data.queue_metadata.len().await, 1);
+
+        guild_data.stale_queue = false;
+        assert_eq!(guild_data.live_queue().len().await, 1);
     }
 }
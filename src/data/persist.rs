@@ -0,0 +1,180 @@
+//! Keeps queues across restarts, see [Config::persistence_dir](crate::Config::persistence_dir).
+//!
+//! Each guild's queue is saved as `queue-<guild id>.json` in the state directory on shutdown,
+//! along with its queue limit override, and loaded back on startup. Only metadata is saved,
+//! so the tracks are re-created from their urls once the bot joins a channel again.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serenity::GuildId;
+use tokio::sync::Mutex;
+
+use super::GuildDataRef;
+use super::TrackMetadata;
+use crate::serenity;
+
+/// Prefix of the saved queue files, followed by the guild id.
+const FILE_PREFIX: &str = "queue-";
+
+/// What's saved for each guild.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedGuild {
+    /// Metadata of the queued tracks.
+    pub tracks: Vec<TrackMetadata>,
+    /// The guild's [queue limit override](super::GuildData::max_queue_len).
+    #[serde(default)]
+    pub max_queue_len: Option<usize>,
+}
+
+/// Save the queue of every guild to `dir`, one json file per guild.
+/// Guilds with nothing to save have their file removed, so it isn't loaded again.
+/// Failures are only logged, since this runs on the way out.
+pub async fn save_queues(guild_data: &Mutex<HashMap<GuildId, GuildDataRef>>, dir: &str) {
+    if let Err(e) = tokio::fs::create_dir_all(dir).await {
+        tracing::error!("Failed to create state directory {dir}. {e}");
+        return;
+    }
+
+    let map = guild_data.lock().await;
+    for (guild_id, data) in map.iter() {
+        let (queue_meta, max_queue_len) = {
+            let data = data.lock().await;
+            (data.queue_metadata.clone(), data.max_queue_len)
+        };
+        let saved = SavedGuild {
+            tracks: queue_meta.snapshot().await,
+            max_queue_len,
+        };
+        let path = Path::new(dir).join(format!("{FILE_PREFIX}{guild_id}.json"));
+
+        let result = if saved.tracks.is_empty() && saved.max_queue_len.is_none() {
+            match tokio::fs::remove_file(&path).await {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                result => result,
+            }
+        } else {
+            let content = serde_json::to_string(&saved).expect("metadata serialization can't fail");
+            tokio::fs::write(&path, content).await
+        };
+        match result {
+            Ok(()) => tracing::info!("Saved {} tracks for guild {guild_id}.", saved.tracks.len()),
+            Err(e) => tracing::error!("Failed to save the queue of guild {guild_id}. {e}"),
+        }
+    }
+}
+
+/// Load the queues saved by [save_queues] from `dir`.
+/// Files that can't be read are logged and skipped.
+pub fn load_queues(dir: &str) -> HashMap<GuildId, SavedGuild> {
+    let mut queues = HashMap::new();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return queues,
+        Err(e) => {
+            tracing::error!("Failed to read state directory {dir}. {e}");
+            return queues;
+        }
+    };
+
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        let Some(guild_id) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix(FILE_PREFIX))
+            .and_then(|id| id.parse::<u64>().ok())
+        else {
+            continue;
+        };
+
+        let saved = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| parse_saved(&content).map_err(|e| e.to_string()));
+        match saved {
+            Ok(saved) => {
+                queues.insert(GuildId::new(guild_id), saved);
+            }
+            Err(e) => tracing::error!("Failed to load {}. {e}", path.display()),
+        }
+    }
+    queues
+}
+
+/// Parse a saved guild, including the plain list of tracks saved by older versions.
+fn parse_saved(content: &str) -> serde_json::Result<SavedGuild> {
+    serde_json::from_str(content).or_else(|e| {
+        serde_json::from_str(content)
+            .map(|tracks| SavedGuild {
+                tracks,
+                max_queue_len: None,
+            })
+            .map_err(|_| e)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::data::GuildData;
+    use crate::data::QueueMeta;
+
+    #[tokio::test]
+    async fn saved_queues_load_again() {
+        let dir = std::env::temp_dir().join(format!("parakeet-persist-{}", Uuid::new_v4()));
+        let dir = dir.to_str().unwrap();
+        let track = TrackMetadata {
+            id: Uuid::new_v4(),
+            title: Some("Song".to_string()),
+            duration: Some(std::time::Duration::from_secs(90)),
+            channel: None,
+            thumbnail_url: None,
+            url: Some("https://example.com/song".to_string()),
+            requested_by: None,
+        };
+
+        let guild = GuildData {
+            queue_metadata: QueueMeta::restore(vec![track.clone()]),
+            ..Default::default()
+        };
+        let empty = GuildData::default();
+        let limited = GuildData {
+            max_queue_len: Some(20),
+            ..Default::default()
+        };
+        let map = Mutex::new(HashMap::from([
+            (GuildId::new(1), Arc::new(Mutex::new(guild))),
+            (GuildId::new(2), Arc::new(Mutex::new(empty))),
+            (GuildId::new(3), Arc::new(Mutex::new(limited))),
+        ]));
+        save_queues(&map, dir).await;
+
+        let loaded = load_queues(dir);
+        std::fs::remove_dir_all(dir).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert!(loaded[&GuildId::new(3)].tracks.is_empty());
+        assert_eq!(loaded[&GuildId::new(3)].max_queue_len, Some(20));
+        let saved = &loaded[&GuildId::new(1)];
+        assert_eq!(saved.max_queue_len, None);
+        let tracks = &saved.tracks;
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].id, track.id);
+        assert_eq!(tracks[0].url, track.url);
+        assert_eq!(tracks[0].duration, track.duration);
+    }
+
+    #[test]
+    fn plain_track_lists_still_load() {
+        let saved = parse_saved("[]").unwrap();
+        assert!(saved.tracks.is_empty());
+        assert_eq!(saved.max_queue_len, None);
+    }
+}
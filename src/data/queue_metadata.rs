@@ -3,15 +3,42 @@
 use std::fmt::Display;
 use std::{collections::VecDeque, fmt::Write};
 
+use std::ops::Range;
 use std::sync::Arc;
+use std::sync::OnceLock;
 use std::time::Duration;
 
 use delegate::delegate;
-use songbird::input::Input;
+use serde::Deserialize;
+use serde::Serialize;
+use songbird::input::AudioStreamError;
+use songbird::input::AuxMetadata;
+use songbird::input::AuxMetadataError;
+use songbird::tracks::Track;
 use tokio::sync::Mutex;
+use uuid::Uuid;
 
+use crate::serenity::UserId;
 use crate::{lib, ParakeetError};
 
+/// Shown for tracks without a title, unless [Config::missing_title](crate::Config::missing_title) is set.
+const DEFAULT_MISSING_TITLE: &str = "<MISSING TITLE>";
+
+/// The configured [missing_title], set once on startup by [set_missing_title].
+static MISSING_TITLE: OnceLock<String> = OnceLock::new();
+
+/// What to show in place of a track's title if it doesn't have one.
+pub fn missing_title() -> &'static str {
+    MISSING_TITLE
+        .get()
+        .map_or(DEFAULT_MISSING_TITLE, String::as_str)
+}
+
+/// Set what [missing_title] returns. Only the first call has an effect.
+pub fn set_missing_title(title: String) {
+    let _ = MISSING_TITLE.set(title);
+}
+
 /// Stores track metadata of the queue.
 /// Internally uses an [Arc], so it's cheap to clone.
 #[derive(Debug, Default, Clone)]
@@ -21,12 +48,66 @@ pub struct QueueMeta {
 }
 
 impl QueueMeta {
+    /// Create a queue holding `tracks`, e.g. ones [saved to disk](super::persist).
+    pub fn restore(tracks: Vec<TrackMetadata>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(tracks.into())),
+        }
+    }
+
     /// Clone the element at the front.
     pub async fn front(&self) -> Option<TrackMetadata> {
         let queue = self.inner.lock().await;
         queue.front().cloned()
     }
 
+    /// Clone the element at `index`.
+    pub async fn get(&self, index: usize) -> Option<TrackMetadata> {
+        let queue = self.inner.lock().await;
+        queue.get(index).cloned()
+    }
+
+    /// Clone the whole queue.
+    pub async fn snapshot(&self) -> Vec<TrackMetadata> {
+        let queue = self.inner.lock().await;
+        queue.iter().cloned().collect()
+    }
+
+    /// Clone the track with the given [TrackMetadata::id].
+    pub async fn get_by_id(&self, id: Uuid) -> Option<TrackMetadata> {
+        let queue = self.inner.lock().await;
+        queue.iter().find(|track| track.id == id).cloned()
+    }
+
+    /// Find the index of the track with the given [TrackMetadata::id].
+    pub async fn position(&self, id: Uuid) -> Option<usize> {
+        let queue = self.inner.lock().await;
+        queue.iter().position(|track| track.id == id)
+    }
+
+    /// Replace the whole queue with `items` at once, returning what was in it.
+    pub async fn replace_all(&self, items: Vec<TrackMetadata>) -> Vec<TrackMetadata> {
+        let mut queue = self.inner.lock().await;
+        std::mem::replace(&mut *queue, items.into()).into()
+    }
+
+    /// Add `meta` right after the track with the given [TrackMetadata::id],
+    /// or to the back of the queue if there's no such track.
+    pub async fn insert_after(&self, id: Option<Uuid>, meta: TrackMetadata) {
+        let mut queue = self.inner.lock().await;
+        let index = id
+            .and_then(|id| queue.iter().position(|track| track.id == id))
+            .map_or(queue.len(), |index| index + 1);
+        queue.insert(index, meta);
+    }
+
+    /// Remove the track with the given [TrackMetadata::id].
+    pub async fn remove_by_id(&self, id: Uuid) -> Option<TrackMetadata> {
+        let mut queue = self.inner.lock().await;
+        let index = queue.iter().position(|track| track.id == id)?;
+        queue.remove(index)
+    }
+
     delegate! {
         to self.inner.lock().await {
             /// Pop the front of the queue.
@@ -38,6 +119,12 @@ impl QueueMeta {
             /// Clear the queue.
             #[await(false)]
             pub async fn clear(&self);
+            /// Number of tracks in the queue.
+            #[await(false)]
+            pub async fn len(&self) -> usize;
+            /// Whether the queue is empty.
+            #[await(false)]
+            pub async fn is_empty(&self) -> bool;
             /// Add to the front of the queue.
             #[await(false)]
             pub async fn push_front(&self, meta: TrackMetadata);
@@ -49,17 +136,96 @@ impl QueueMeta {
 }
 
 impl QueueMeta {
-    /// Implement "Display" on [QueueMeta]
-    pub async fn display_string(&self) -> String {
-        let queue = { self.inner.lock().await };
+    /// Display the 0-based `page` of the whole queue with `per_page` lines,
+    /// along with how many pages there are. See [QueuePages::render].
+    pub async fn display_page(&self, page: usize, per_page: usize) -> (String, usize) {
+        let pages = self.pages(|_| true, false, per_page).await;
+        (pages.render(page, None), pages.len())
+    }
 
-        if queue.is_empty() {
+    /// Split the tracks matching `filter` into pages of `per_page` lines to display.
+    /// If `collapse` is set, repeats in a row are listed once, see [consecutive_runs].
+    pub async fn pages(
+        &self,
+        filter: impl Fn(&TrackMetadata) -> bool,
+        collapse: bool,
+        per_page: usize,
+    ) -> QueuePages {
+        let tracks = self.snapshot().await;
+        let runs = if collapse {
+            consecutive_runs(&tracks)
+        } else {
+            (0..tracks.len()).map(|num| num..num + 1).collect()
+        };
+        let rows = runs
+            .into_iter()
+            .filter(|run| filter(&tracks[run.start]))
+            .collect();
+
+        QueuePages {
+            tracks,
+            rows,
+            per_page: per_page.max(1),
+        }
+    }
+}
+
+/// A snapshot of the queue split into pages, see [QueueMeta::pages].
+/// Only the lines of a page are formatted, and only once it's [rendered](QueuePages::render).
+#[derive(Debug)]
+pub struct QueuePages {
+    /// The whole queue when the pages were made.
+    tracks: Vec<TrackMetadata>,
+    /// The tracks listed on each line, by their position in `tracks`.
+    rows: Vec<Range<usize>>,
+    /// How many lines each page has.
+    per_page: usize,
+}
+
+impl QueuePages {
+    /// How many pages there are, at least 1 even if there's nothing to list.
+    pub fn len(&self) -> usize {
+        self.rows.len().div_ceil(self.per_page).max(1)
+    }
+
+    /// Display the 0-based `page`, or the last page if it's out of range.
+    /// Tracks keep their position in the whole queue as their number.
+    /// The current track (position 0) is marked, along with how far along it is (`elapsed`)
+    /// if that's known.
+    pub fn render(&self, page: usize, elapsed: Option<Duration>) -> String {
+        if self.tracks.is_empty() {
             return "Empty queue!".to_string();
         }
+        if self.rows.is_empty() {
+            return "No matching tracks!".to_string();
+        }
 
+        let page = page.min(self.len() - 1);
         let mut buffer = String::new();
-        for (num, track) in queue.iter().enumerate() {
-            let next_line = format!("`{num}.` {track}");
+        for run in self
+            .rows
+            .iter()
+            .skip(page * self.per_page)
+            .take(self.per_page)
+        {
+            let (num, track) = (run.start, &self.tracks[run.start]);
+            let mut next_line = match run.len() {
+                1 => format!("`{num}.` {track}"),
+                count => format!("`{num}.` {track} (x{count})"),
+            };
+            if num == 0 {
+                next_line = match elapsed {
+                    Some(elapsed) => {
+                        let elapsed = lib::format_duration(&elapsed);
+                        format!("▶ {next_line} (at {elapsed})")
+                    }
+                    None => format!("▶ {next_line}"),
+                };
+            }
+            // Mentions in embeds show the user's name without pinging them.
+            if let Some(user) = track.requested_by {
+                write!(next_line, " • <@{user}>").expect("write to string buffer can't fail");
+            }
 
             // An embed has a limit of 4096 chars
             if buffer.len() + next_line.len() > 4096 {
@@ -71,9 +237,34 @@ impl QueueMeta {
     }
 }
 
+/// The ids of repeats in a row in `tracks`, leaving the first of each run of repeats.
+pub fn consecutive_duplicates(tracks: &[TrackMetadata]) -> Vec<Uuid> {
+    consecutive_runs(tracks)
+        .into_iter()
+        .flat_map(|run| tracks[run].iter().skip(1).map(|track| track.id))
+        .collect()
+}
+
+/// Split `tracks` into runs of the same [TrackMetadata::url] in a row.
+/// Tracks without an url are never repeats.
+fn consecutive_runs(tracks: &[TrackMetadata]) -> Vec<Range<usize>> {
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (num, track) in tracks.iter().enumerate() {
+        match runs.last_mut() {
+            Some(run) if track.url.is_some() && tracks[run.start].url == track.url => {
+                run.end = num + 1
+            }
+            _ => runs.push(num..num + 1),
+        }
+    }
+    runs
+}
+
 /// Metadata for a track in the queue.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrackMetadata {
+    /// Identifies the track in the queue, same as the songbird [Track::uuid].
+    pub id: Uuid,
     /// Title of the track.
     pub title: Option<String>,
     /// Duration of the track.
@@ -84,30 +275,88 @@ pub struct TrackMetadata {
     pub thumbnail_url: Option<String>,
     /// Url to source
     pub url: Option<String>,
+    /// The user who added this track, if known.
+    pub requested_by: Option<UserId>,
 }
 
 impl TrackMetadata {
-    /// Try to get [TrackMetadata] from [Input]
-    pub async fn from_input(input: &mut Input) -> Result<Self, ParakeetError> {
-        let meta = input.aux_metadata().await?;
+    /// Try to get [TrackMetadata] from a [Track]'s input.
+    /// Inputs that can't have metadata at all (e.g. plain http) get empty metadata.
+    pub async fn from_track(track: &mut Track) -> Result<Self, ParakeetError> {
+        let meta = match track.input.aux_metadata().await {
+            Err(AuxMetadataError::Retrieve(AudioStreamError::Unsupported)) => {
+                AuxMetadata::default()
+            }
+            meta => meta?,
+        };
+        Ok(Self::from_aux(track, meta))
+    }
+
+    /// [TrackMetadata] for a [Track] whose metadata was already looked up.
+    pub fn from_aux(track: &Track, meta: AuxMetadata) -> Self {
+        let id = track.uuid;
         let title = meta.title;
         let duration = meta.duration;
         let channel = meta.channel;
         let thumbnail_url = meta.thumbnail;
         let url = meta.source_url;
-        Ok(TrackMetadata {
+        TrackMetadata {
+            id,
             title,
             duration,
             channel,
             thumbnail_url,
             url,
-        })
+            requested_by: None,
+        }
+    }
+
+    /// The [AuxMetadata] this was made from, see [from_aux](TrackMetadata::from_aux).
+    pub fn to_aux(&self) -> AuxMetadata {
+        AuxMetadata {
+            title: self.title.clone(),
+            channel: self.channel.clone(),
+            duration: self.duration,
+            source_url: self.url.clone(),
+            thumbnail: self.thumbnail_url.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// The combined duration of `tracks`, skipping the ones with an unknown duration.
+    pub fn total_duration<'a>(tracks: impl IntoIterator<Item = &'a TrackMetadata>) -> Duration {
+        tracks.into_iter().filter_map(|track| track.duration).sum()
+    }
+
+    /// How many of `tracks` each requester added and their combined duration,
+    /// most tracks first. Tracks without a known requester are grouped under `None`.
+    pub fn per_requester(tracks: &[TrackMetadata]) -> Vec<(Option<UserId>, usize, Duration)> {
+        let mut totals: Vec<(Option<UserId>, usize, Duration)> = Vec::new();
+        for track in tracks {
+            let duration = track.duration.unwrap_or_default();
+            match totals
+                .iter_mut()
+                .find(|(user, _, _)| *user == track.requested_by)
+            {
+                Some((_, count, total)) => {
+                    *count += 1;
+                    *total += duration;
+                }
+                None => totals.push((track.requested_by, 1, duration)),
+            }
+        }
+        // Stable, so ties stay in queue order.
+        totals.sort_by_key(|(_, count, _)| std::cmp::Reverse(*count));
+        totals
     }
 }
 
 impl Display for TrackMetadata {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let title = self.title.clone().unwrap_or("<MISSING TITLE>".to_string());
+        let title = match &self.title {
+            Some(title) => lib::truncate_title(title, lib::MAX_TITLE_LEN),
+            None => missing_title().to_string(),
+        };
         let channel = self.channel.clone().unwrap_or_default();
         let duration = match self.duration {
             None => String::new(),
@@ -121,3 +370,163 @@ impl Display for TrackMetadata {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A placeholder track with the given url.
+    fn track(url: Option<&str>) -> TrackMetadata {
+        TrackMetadata {
+            id: Uuid::nil(),
+            title: None,
+            duration: None,
+            channel: None,
+            thumbnail_url: None,
+            url: url.map(str::to_string),
+            requested_by: None,
+        }
+    }
+
+    #[test]
+    fn requesters_are_totalled() {
+        let by = |user: Option<u64>, secs: Option<u64>| TrackMetadata {
+            requested_by: user.map(UserId::new),
+            duration: secs.map(Duration::from_secs),
+            ..track(None)
+        };
+        let tracks = [
+            by(Some(1), Some(60)),
+            by(None, Some(10)),
+            by(Some(2), Some(30)),
+            by(Some(2), None),
+            by(Some(1), Some(60)),
+            by(Some(2), Some(30)),
+        ];
+
+        let totals = TrackMetadata::per_requester(&tracks);
+        assert_eq!(
+            totals,
+            vec![
+                (Some(UserId::new(2)), 3, Duration::from_secs(60)),
+                (Some(UserId::new(1)), 2, Duration::from_secs(120)),
+                (None, 1, Duration::from_secs(10)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_only_render_their_lines() {
+        let queue = QueueMeta::default();
+        for _ in 0..25 {
+            queue.push_back(track(None)).await;
+        }
+
+        let pages = queue.pages(|_| true, false, 10).await;
+        assert_eq!(pages.len(), 3);
+        let render = |page| pages.render(page, Some(Duration::from_secs(83)));
+        assert_eq!(render(0).lines().count(), 10);
+        assert!(render(0).starts_with("▶ `0.`"));
+        assert!(render(0)
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with("(at [01m:23s])"));
+        assert!(render(0).lines().nth(1).unwrap().starts_with("`1.`"));
+        assert!(render(2).starts_with("`20.`"));
+        assert_eq!(render(2).lines().count(), 5);
+
+        let none = queue.pages(|_| false, false, 10).await;
+        assert_eq!(none.len(), 1);
+        assert_eq!(none.render(0, None), "No matching tracks!");
+
+        let (text, count) = queue.display_page(1, 10).await;
+        assert_eq!(count, 3);
+        assert!(!text.contains("<@"));
+        assert!(text.starts_with("`10.`"));
+        assert!(queue.display_page(0, 10).await.0.starts_with("▶ `0.`"));
+    }
+
+    #[tokio::test]
+    async fn lines_mention_requesters() {
+        let queue = QueueMeta::default();
+        queue.push_back(track(None)).await;
+        queue
+            .push_back(TrackMetadata {
+                requested_by: Some(UserId::new(42)),
+                ..track(None)
+            })
+            .await;
+
+        let (text, _) = queue.display_page(0, 10).await;
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(!lines[0].contains("<@"));
+        assert!(lines[1].ends_with(" • <@42>"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn replace_all_is_atomic() {
+        let titled = |title: &str, len: usize| -> Vec<TrackMetadata> {
+            (0..len)
+                .map(|_| TrackMetadata {
+                    title: Some(title.to_string()),
+                    ..track(None)
+                })
+                .collect()
+        };
+        let queue = QueueMeta::default();
+        queue.replace_all(titled("a", 5)).await;
+
+        let writer = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                for i in 0..200 {
+                    let items = if i % 2 == 0 {
+                        titled("b", 7)
+                    } else {
+                        titled("a", 5)
+                    };
+                    queue.replace_all(items).await;
+                }
+            })
+        };
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let queue = queue.clone();
+                tokio::spawn(async move {
+                    for _ in 0..200 {
+                        // Never a mix of the old and new queue.
+                        let snapshot = queue.snapshot().await;
+                        let title = snapshot[0].title.clone();
+                        assert!(snapshot.iter().all(|track| track.title == title));
+                        let expected = if title.as_deref() == Some("a") { 5 } else { 7 };
+                        assert_eq!(snapshot.len(), expected);
+                    }
+                })
+            })
+            .collect();
+
+        writer.await.unwrap();
+        for reader in readers {
+            reader.await.unwrap();
+        }
+    }
+
+    #[test]
+    fn only_repeats_in_a_row_are_collapsed() {
+        let tracks = [
+            track(Some("a")),
+            track(Some("a")),
+            track(Some("a")),
+            track(Some("b")),
+            track(Some("a")),
+            track(None),
+            track(None),
+        ];
+
+        assert_eq!(
+            consecutive_runs(&tracks),
+            vec![0..3, 3..4, 4..5, 5..6, 6..7]
+        );
+    }
+}
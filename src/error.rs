@@ -1,10 +1,12 @@
 //! Errors used in this crate.
 
+use std::collections::HashMap;
 use std::time::Duration;
 
 use thiserror::Error;
 
 use crate::lib::format_duration;
+use crate::lib::youtube::Unavailable;
 use crate::serenity;
 
 /// Helper macro to format Option<String> types
@@ -67,6 +69,15 @@ pub enum ParakeetError {
     /// Track manipulation error
     #[error(transparent)]
     ControlError(#[from] songbird::tracks::ControlError),
+    /// Errors from external apis, see [reqwest].
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// Failed to change the log filter.
+    #[error(transparent)]
+    ReloadError(#[from] tracing_subscriber::reload::Error),
+    /// A [queue transaction](crate::lib::call::transaction) couldn't be applied, nothing was changed.
+    #[error("Queue change was rolled back. Reason: {reason}")]
+    QueueTransaction { reason: String },
 }
 
 /// Make debug implementation return the [std::fmt::Display] implementation to
@@ -84,6 +95,12 @@ pub enum UserError {
     /// Could not find the user's voice channel.
     #[error("Couldn't find you in a voice channel!")]
     NotInVoice,
+    /// The user's voice channel has no room for the bot.
+    #[error("Your voice channel is full!")]
+    ChannelFull,
+    /// The user isn't in the bot's voice channel.
+    #[error("You need to be in the same voice channel as me!")]
+    NotInSameChannel,
     /// Could not find the user's guild.
     #[error("Couldn't find your server!")]
     NotInGuild,
@@ -96,9 +113,27 @@ pub enum UserError {
         /// Why the search failed
         reason: String,
     },
+    /// yt-dlp couldn't get a video for a known reason.
+    #[error("{reason}")]
+    VideoUnavailable {
+        /// Why the video is unavailable
+        reason: Unavailable,
+    },
     /// User tried to use an unsupported platform.
     #[error("Unsupported platform, sorry! :(")]
     UnsupportedPlatform,
+    /// User gave a file that isn't audio or video.
+    #[error("I can only play audio or video files, not {content_type}.")]
+    UnsupportedFile {
+        /// The file's reported content type
+        content_type: String,
+    },
+    /// User gave a file that's bigger than the command allows.
+    #[error("That file is too big, it can be at most {} KiB.", limit / 1024)]
+    FileTooLarge {
+        /// The largest allowed size in bytes
+        limit: u32,
+    },
     /// User left out a required subcommand
     #[error("Missing a subcommand: {subcmds}")]
     MissingSubcommand {
@@ -144,6 +179,43 @@ pub enum UserError {
     /// Queue already empty.
     #[error("Nothing in the queue!")]
     EmptyQueue,
+    /// No track has finished playing that could be played again.
+    #[error("Nothing to replay, no track has finished yet!")]
+    NoHistory,
+    /// Queue can't fit any more tracks.
+    #[error("The queue is full! ({limit} tracks max)")]
+    QueueFull {
+        /// How many tracks the queue can hold
+        limit: usize,
+    },
+}
+
+impl UserError {
+    /// The key used to override this error's message in the config.
+    /// Only the errors users run into most often can be overridden.
+    pub fn key(&self) -> Option<&'static str> {
+        match self {
+            UserError::NotInVoice => Some("not_in_voice"),
+            UserError::ChannelFull => Some("channel_full"),
+            UserError::NotInSameChannel => Some("not_in_same_channel"),
+            UserError::NotInGuild => Some("not_in_guild"),
+            UserError::NoActiveCall => Some("no_active_call"),
+            UserError::UnsupportedPlatform => Some("unsupported_platform"),
+            UserError::UnsupportedFile { .. } => Some("unsupported_file"),
+            UserError::VideoUnavailable { .. } => Some("video_unavailable"),
+            UserError::EmptyQueue => Some("empty_queue"),
+            UserError::QueueFull { .. } => Some("queue_full"),
+            _ => None,
+        }
+    }
+
+    /// Display this error, preferring an override from `overrides` over the default message.
+    pub fn display_with(&self, overrides: &HashMap<String, String>) -> String {
+        self.key()
+            .and_then(|key| overrides.get(key))
+            .cloned()
+            .unwrap_or_else(|| self.to_string())
+    }
 }
 
 /// Errors that can occur when reading/writing/parsing a config file.
@@ -211,4 +283,27 @@ mod tests {
         let expected = format!("This has nothing");
         assert_eq!(val, expected);
     }
+
+    #[test]
+    fn test_display_with() {
+        let overrides = HashMap::from([("not_in_voice".to_string(), "Hop in a call!".to_string())]);
+
+        // Overridden message
+        assert_eq!(
+            UserError::NotInVoice.display_with(&overrides),
+            "Hop in a call!"
+        );
+
+        // Overridable but missing from the map falls back to the default
+        assert_eq!(
+            UserError::EmptyQueue.display_with(&overrides),
+            UserError::EmptyQueue.to_string()
+        );
+
+        // Not overridable at all
+        assert_eq!(
+            UserError::NotOwner.display_with(&overrides),
+            UserError::NotOwner.to_string()
+        );
+    }
 }
@@ -3,19 +3,48 @@
 //! Currently the bot monitors for the following:
 //! - On idle (alone for some time), the bot stops and deletes the queues, then disconnects.
 //! - On disconnect, the bot stops, deletes queues, and removes all global event handlers.
+//!
+//! # Lock ordering
+//! To avoid deadlocks, locks are always acquired in this order:
+//! 1. The [CallRef].
+//! 2. [Data::guild_data](crate::data::Data::guild_data), then a [GuildData](crate::data::GuildData).
+//! 3. The [QueueMeta].
+//!
+//! [GetData::queue_meta] releases the [GuildData](crate::data::GuildData) lock before returning,
+//! so it's fine to call before locking the call.
+//! Songbird's own [TrackQueue](songbird::tracks::TrackQueue) lock is synchronous and is only taken
+//! inside its methods, so it's never held across an `.await`.
 
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
+use futures::future::join_all;
+use songbird::input::AuxMetadata;
+use songbird::input::HttpRequest;
 use songbird::input::Input;
+use songbird::input::YoutubeDl;
+use songbird::tracks::LoopState;
+use songbird::tracks::Track;
 use songbird::tracks::TrackHandle;
 use tokio::sync::Mutex;
+use tokio::sync::Semaphore;
 use tracing::instrument;
+use uuid::Uuid;
 
+use crate::data::consecutive_duplicates;
+use crate::data::LoopMode;
+use crate::data::QueueMeta;
 use crate::data::TrackMetadata;
 use crate::error::UserError;
 
 use crate::data::GetData;
+use crate::lib;
 use crate::lib::events;
+use crate::serenity;
 use crate::Context;
 use crate::ParakeetError;
 
@@ -24,6 +53,12 @@ pub type CallRef = Arc<Mutex<songbird::Call>>;
 /// Convenience type alias for [songbird::Songbird].
 type Manager = Arc<songbird::Songbird>;
 
+/// Marks a [TrackHandle] as a one-off track that isn't part of the queue, see [play_transient].
+struct TransientKey;
+impl serenity::prelude::TypeMapKey for TransientKey {
+    type Value = ();
+}
+
 /// Alias for discovery.
 /// Must always use this function to initialize a call.
 pub use events::init_global_events as get_call;
@@ -40,61 +75,1224 @@ pub async fn get_manager(ctx: &Context<'_>) -> Result<Manager, ParakeetError> {
 /// Join the author's voice channel and register global songbird events.
 #[instrument(skip(ctx), fields(author=%ctx.author(), guild=?ctx.guild_id(), channel=?ctx.channel_id()))]
 pub async fn join_author(ctx: &Context<'_>) -> Result<CallRef, ParakeetError> {
+    let author = ctx.author();
+    let guild_id = ctx.guild_id().ok_or(UserError::NotInGuild)?;
+    let channel_id = author_channel(ctx)?;
+
+    tracing::info!(
+        "Joining {user} at {guild}",
+        user = author.name,
+        guild = guild_id.name(ctx).unwrap_or("<MISSING GUILD>".to_string())
+    );
+
+    join_channel(ctx, channel_id).await
+}
+
+/// The voice channel the [Context]'s author is in.
+pub fn author_channel(ctx: &Context<'_>) -> Result<serenity::ChannelId, UserError> {
+    let guild = ctx.guild().ok_or(UserError::NotInGuild)?;
+    guild
+        .voice_states
+        .get(&ctx.author().id)
+        .and_then(|vs| vs.channel_id)
+        .ok_or(UserError::NotInVoice)
+}
+
+/// The voice channel the bot is in, if any. Doesn't join or create a call.
+pub async fn bot_channel(ctx: &Context<'_>) -> Result<Option<serenity::ChannelId>, ParakeetError> {
+    let guild_id = ctx.guild_id().ok_or(UserError::GuildOnly)?;
+    let channel = match get_manager(ctx).await?.get(guild_id) {
+        Some(call) => call.lock().await.current_channel(),
+        None => None,
+    };
+    // Convert songbird::ChannelId -> u64 -> serenity::ChannelId
+    Ok(channel.map(|c| serenity::ChannelId::from(c.0)))
+}
+
+/// Join a voice channel in the [Context]'s guild and register global songbird events.
+pub async fn join_channel(
+    ctx: &Context<'_>,
+    channel_id: serenity::ChannelId,
+) -> Result<CallRef, ParakeetError> {
     // Initializes only once
     events::init_global_events(ctx).await?;
 
     let manager = get_manager(ctx).await?;
-    let author = ctx.author();
 
-    // Try to find the user's guild
-    let (guild_id, voice_states) = match ctx.guild() {
-        Some(guild) => (guild.id, guild.voice_states.clone()),
-        None => Err(UserError::NotInGuild)?,
+    let guild_id = {
+        let guild = ctx.guild().ok_or(UserError::NotInGuild)?;
+        check_capacity(&guild, channel_id, ctx.cache().current_user().id)?;
+        guild.id
     };
 
-    // Try to find the user's voice channel
-    let channel_id = match voice_states.get(&author.id) {
-        Some(vs) => match vs.channel_id {
-            Some(id) => id,
-            None => Err(UserError::NotInVoice)?,
-        },
-        None => Err(UserError::NotInVoice)?,
+    // Left over metadata only matters when coming back from a disconnect.
+    let was_connected = match manager.get(guild_id) {
+        Some(call) => call.lock().await.current_channel().is_some(),
+        None => false,
     };
 
-    tracing::info!(
-        "Joining {user} at {guild}",
-        user = author.name,
-        guild = guild_id.name(ctx).unwrap_or("<MISSING GUILD>".to_string())
-    );
-
     // Try to join the call.
     let call = manager.join(guild_id, channel_id).await?;
+    if !was_connected {
+        // The guild's data may be hours old, which would count as paused on the first idle check.
+        ctx.guild_data().await?.lock().await.joined();
+        greet(ctx, &call).await;
+        handle_stale_queue(ctx, &call).await?;
+    }
+
+    if ctx.data().config.self_deafen() {
+        // Not being deafened only costs bandwidth, so keep going.
+        if let Err(e) = call.lock().await.deafen(true).await {
+            tracing::warn!("Failed to deafen: {e}");
+        }
+    }
 
     Ok(call)
 }
 
-/// Add [Input] to the back of the queue.
-pub async fn enqueue(
+/// Send the configured join message and play the configured join sound, if there are any.
+/// The queue is held until the join sound is over.
+/// Neither is worth failing the join over, so problems are only logged.
+async fn greet(ctx: &Context<'_>, call: &CallRef) {
+    let config = &ctx.data().config;
+
+    if let Some(message) = config.join_message() {
+        if let Err(e) = ctx.channel_id().say(ctx, message).await {
+            tracing::warn!("Failed to send join message: {e}");
+        }
+    }
+
+    if let Some(path) = config.join_sound_path() {
+        if !tokio::fs::try_exists(path).await.unwrap_or(false) {
+            tracing::warn!("Join sound {path} doesn't exist, skipping it.");
+            return;
+        }
+        let input: Input = songbird::input::File::new(PathBuf::from(path)).into();
+        let sound = play_transient(call, input).await;
+        events::hold_queue_for(call, &sound).await;
+    }
+}
+
+/// Deal with metadata left over from before a disconnect or restart, which has no tracks left to play.
+/// Depending on the config, it's either cleared or its tracks are queued again.
+async fn handle_stale_queue(ctx: &Context<'_>, call: &CallRef) -> Result<(), ParakeetError> {
+    // Whatever happens below, the queue is live again afterwards.
+    ctx.guild_data().await?.lock().await.stale_queue = false;
+    let queue_meta = ctx.queue_meta().await?;
+    let stale = {
+        let call = call.lock().await;
+        if !call.queue().is_empty() || queue_meta.is_empty().await {
+            return Ok(());
+        }
+        let stale = queue_meta.snapshot().await;
+        queue_meta.clear().await;
+        stale
+    };
+
+    // Queues loaded on startup were never played in this session, so they always resume.
+    let restored = std::mem::take(&mut ctx.guild_data().await?.lock().await.restored_queue);
+    if !restored && !ctx.data().config.rejoin_resumes_queue() {
+        tracing::info!("Clearing {} stale tracks.", stale.len());
+        return Ok(());
+    }
+
+    let (volume, loop_mode) = {
+        let guild_data = ctx.guild_data().await?;
+        let guild_data = guild_data.lock().await;
+        (guild_data.volume, guild_data.loop_mode)
+    };
+    let http_client = ctx.http_client().await;
+    let tracks = rebuild_tracks(http_client, volume, loop_mode, stale);
+    tracing::info!("Resuming {} stale tracks.", tracks.len());
+    replace_queue(call, &queue_meta, tracks).await;
+    Ok(())
+}
+
+/// An [Input] that re-creates a track from its [url](TrackMetadata::url).
+/// Discord attachments, e.g. from `/playfile`, are streamed directly like they were at first.
+pub fn rebuild_input(http_client: reqwest::Client, url: String) -> Input {
+    if is_attachment(&url) {
+        HttpRequest::new(http_client, url).into()
+    } else {
+        YoutubeDl::new(http_client, url).into()
+    }
+}
+
+/// Whether `url` points to a file uploaded to discord.
+fn is_attachment(url: &str) -> bool {
+    let Ok(url) = url::Url::parse(url) else {
+        return false;
+    };
+    matches!(
+        url.host_str(),
+        Some("cdn.discordapp.com" | "media.discordapp.net")
+    ) && url.path().starts_with("/attachments/")
+}
+
+/// Re-create `tracks` from their urls, keeping their metadata.
+/// The tracks are set up like [new_tracks] does, with the given `volume` and `loop_mode`.
+/// Tracks without an url can't be re-created, so they're dropped.
+pub fn rebuild_tracks(
+    http_client: reqwest::Client,
+    volume: f32,
+    loop_mode: LoopMode,
+    tracks: Vec<TrackMetadata>,
+) -> Vec<(Track, TrackMetadata)> {
+    tracks
+        .into_iter()
+        .filter_map(|meta| {
+            let input = rebuild_input(http_client.clone(), meta.url.clone()?);
+            let track = new_track(input, volume, loop_mode);
+            // Keep the metadata as is, but match the new track.
+            let meta = TrackMetadata {
+                id: track.uuid,
+                ..meta
+            };
+            Some((track, meta))
+        })
+        .collect()
+}
+
+/// Replace the whole queue with `tracks`, stopping everything that was queued.
+/// Returns the metadata of the replaced tracks.
+pub async fn replace_queue(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    tracks: Vec<(Track, TrackMetadata)>,
+) -> Vec<TrackMetadata> {
+    let mut call = call.lock().await;
+    // Stopped tracks have no metadata left to remove by the time their end is handled.
+    call.queue().stop();
+
+    let (tracks, metadata): (Vec<_>, Vec<_>) = tracks.into_iter().unzip();
+    let replaced = queue_meta.replace_all(metadata).await;
+    for track in tracks {
+        call.enqueue(track).await;
+    }
+    replaced
+}
+
+/// Check that the author is in the same voice channel as the bot, for commands that control playback.
+/// Passes if the bot isn't in a voice channel, or if this is disabled in the config.
+pub fn require_same_channel(ctx: &Context<'_>) -> Result<(), UserError> {
+    if !ctx.data().config.require_same_channel() {
+        return Ok(());
+    }
+
+    let bot_id = ctx.cache().current_user().id;
+    let guild = ctx.guild().ok_or(UserError::NotInGuild)?;
+    let channel_of = |user_id| {
+        guild
+            .voice_states
+            .get(&user_id)
+            .and_then(|vs| vs.channel_id)
+    };
+
+    match channel_of(bot_id) {
+        None => Ok(()),
+        Some(channel_id) if channel_of(ctx.author().id) == Some(channel_id) => Ok(()),
+        Some(_) => Err(UserError::NotInSameChannel),
+    }
+}
+
+/// Check that the bot can fit in the voice channel `channel_id`.
+/// Full channels can still be joined if the bot is already in it or can move members.
+fn check_capacity(
+    guild: &serenity::Guild,
+    channel_id: serenity::ChannelId,
+    bot_id: serenity::UserId,
+) -> Result<(), UserError> {
+    // Without a known limit, let songbird try to join.
+    let Some(channel) = guild.channels.get(&channel_id) else {
+        return Ok(());
+    };
+    let Some(limit) = channel.user_limit.filter(|limit| *limit > 0) else {
+        return Ok(());
+    };
+
+    let occupants: Vec<_> = guild
+        .voice_states
+        .values()
+        .filter(|vs| vs.channel_id == Some(channel_id))
+        .collect();
+    if occupants.iter().any(|vs| vs.user_id == bot_id) {
+        return Ok(());
+    }
+    if (occupants.len() as u32) < limit {
+        return Ok(());
+    }
+
+    let can_bypass = guild
+        .members
+        .get(&bot_id)
+        .map(|member| guild.user_permissions_in(channel, member))
+        .is_some_and(|perms| perms.move_members() || perms.administrator());
+    if can_bypass {
+        Ok(())
+    } else {
+        Err(UserError::ChannelFull)
+    }
+}
+
+/// How far along the current track is, or zero if nothing is playing.
+/// Doesn't join or create a call.
+pub async fn current_position(ctx: &Context<'_>) -> Result<Duration, ParakeetError> {
+    let guild_id = ctx.guild_id().ok_or(UserError::GuildOnly)?;
+    let current = match get_manager(ctx).await?.get(guild_id) {
+        Some(call) => call.lock().await.queue().current(),
+        None => None,
+    };
+    // The current track may end while checking, in which case nothing has elapsed.
+    Ok(match current {
+        Some(handle) => handle
+            .get_info()
+            .await
+            .map(|info| info.position)
+            .unwrap_or_default(),
+        None => Duration::ZERO,
+    })
+}
+
+/// Add [Input] to the back of the queue, using `meta` instead of looking it up again.
+pub async fn enqueue_known(
     ctx: &Context<'_>,
     call: &CallRef,
-    mut input: Input,
+    input: Input,
+    meta: AuxMetadata,
 ) -> Result<TrackHandle, ParakeetError> {
     tracing::debug!("Adding to the queue.");
 
-    let queue_meta = {
+    let queue_meta = ctx.queue_meta().await?;
+    let (track, metadata) = prepare_track(ctx, &queue_meta, input, meta).await?;
+    Ok(enqueue_with_meta(call, &queue_meta, track, metadata).await)
+}
+
+/// Add [Input] right after the current track, or start playing it if nothing is.
+/// `meta` is used instead of looking it up again.
+pub async fn enqueue_front(
+    ctx: &Context<'_>,
+    call: &CallRef,
+    input: Input,
+    meta: AuxMetadata,
+) -> Result<TrackHandle, ParakeetError> {
+    tracing::debug!("Adding to the front of the queue.");
+
+    let queue_meta = ctx.queue_meta().await?;
+    let (track, metadata) = prepare_track(ctx, &queue_meta, input, meta).await?;
+    Ok(enqueue_front_with_meta(call, &queue_meta, track, metadata).await)
+}
+
+/// Add a [Track] and its [TrackMetadata] right after the current track.
+/// Like [enqueue_with_meta], both queues are updated while holding the call lock,
+/// and nothing can fail once the track is added.
+async fn enqueue_front_with_meta(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    track: Track,
+    metadata: TrackMetadata,
+) -> TrackHandle {
+    let id = metadata.id;
+    let mut call = call.lock().await;
+    // With nothing playing, the track starts right away and goes to the back of the metadata.
+    let current = call.queue().current().map(|handle| handle.uuid());
+    queue_meta.insert_after(current, metadata).await;
+    let handle = call.enqueue(track).await;
+    // Songbird only adds to the back, so the track is moved up behind the current one.
+    call.queue().modify_queue(|tracks| {
+        if let Some(from) = tracks.iter().position(|track| track.uuid() == id) {
+            lib::move_in_deque(tracks, from, from.min(1));
+        }
+    });
+    handle
+}
+
+/// How many metadata lookups [enqueue_all] runs at once, also used for other bulk searches.
+pub const METADATA_LOOKUPS: usize = 4;
+
+/// Create a [Track] from [Input] with the guild's settings applied, along with its metadata.
+/// `meta` was already looked up, so it isn't looked up again. Fails if the queue is already full.
+async fn prepare_track(
+    ctx: &Context<'_>,
+    queue_meta: &QueueMeta,
+    input: Input,
+    meta: AuxMetadata,
+) -> Result<(Track, TrackMetadata), ParakeetError> {
+    let limit = max_queue_len(ctx).await?;
+    if queue_meta.len().await >= limit {
+        Err(UserError::QueueFull { limit })?
+    }
+
+    let track = new_tracks(ctx, vec![input]).await?.remove(0);
+    let mut metadata = TrackMetadata::from_aux(&track, meta);
+    metadata.requested_by = Some(ctx.author().id);
+
+    Ok((track, metadata))
+}
+
+/// Create a [Track] for each [Input], with the guild's volume and loop mode applied.
+async fn new_tracks(ctx: &Context<'_>, inputs: Vec<Input>) -> Result<Vec<Track>, ParakeetError> {
+    // Set on the track itself, so it starts at the right volume instead of jumping to it.
+    let (volume, loop_mode) = {
         let guild_data = ctx.guild_data().await?;
-        let queue = guild_data.lock().await;
-        queue.queue_metadata.clone()
+        let mut guild_data = guild_data.lock().await;
+        guild_data.announce_channel = Some(ctx.channel_id());
+        guild_data.mark_progress();
+        (guild_data.volume, guild_data.loop_mode)
+    };
+    Ok(inputs
+        .into_iter()
+        .map(|input| new_track(input, volume, loop_mode))
+        .collect())
+}
+
+/// Create a [Track] from [Input] starting at `volume`, looping forever in [LoopMode::Track].
+fn new_track(input: Input, volume: f32, loop_mode: LoopMode) -> Track {
+    let track = Track::new(input).volume(volume);
+    match loop_mode {
+        LoopMode::Track => track.loops(LoopState::Infinite),
+        LoopMode::Off | LoopMode::Queue => track,
+    }
+}
+
+/// What happened to the inputs given to [enqueue_all].
+#[derive(Debug)]
+pub struct Enqueued {
+    /// The tracks that were added, in the order they were given.
+    pub added: Vec<TrackHandle>,
+    /// The index of each input that couldn't be loaded, with why.
+    pub failed: Vec<(usize, ParakeetError)>,
+    /// How many inputs weren't added because the queue was full.
+    pub overflow: usize,
+    /// How many tracks the queue could hold.
+    pub limit: usize,
+}
+
+impl Enqueued {
+    /// A line about the tracks that didn't fit in the queue, if any didn't.
+    pub fn overflow_note(&self) -> Option<String> {
+        (self.overflow > 0).then(|| {
+            format!(
+                "Queue full (limit {}), {} tracks not added.",
+                self.limit, self.overflow
+            )
+        })
+    }
+}
+
+/// Add every [Input] to the back of the queue, each with a `fallback_title` used if its metadata has no title.
+///
+/// Metadata is looked up concurrently, at most [METADATA_LOOKUPS] at once since each lookup may
+/// spawn the search backend. The tracks are still queued in the order they were given,
+/// once every lookup is done. Inputs that don't fit in the queue are counted without a lookup,
+/// and once the queue is full nothing after it is added.
+pub async fn enqueue_all(
+    ctx: &Context<'_>,
+    call: &CallRef,
+    inputs: Vec<(Input, Option<String>)>,
+) -> Result<Enqueued, ParakeetError> {
+    tracing::debug!("Adding {} tracks to the queue.", inputs.len());
+
+    let queue_meta = ctx.queue_meta().await?;
+    let limit = max_queue_len(ctx).await?;
+    let room = limit.saturating_sub(queue_meta.len().await);
+
+    let (inputs, fallback_titles): (Vec<_>, Vec<_>) = inputs.into_iter().unzip();
+    let overflow = inputs.len().saturating_sub(room);
+    let tracks = new_tracks(ctx, inputs.into_iter().take(room).collect()).await?;
+
+    let author = ctx.author().id;
+    let permits = Semaphore::new(METADATA_LOOKUPS);
+    let lookups = tracks
+        .into_iter()
+        .zip(fallback_titles)
+        .map(|(mut track, fallback_title)| {
+            let permits = &permits;
+            async move {
+                let _permit = permits
+                    .acquire()
+                    .await
+                    .expect("the semaphore is never closed");
+                let metadata = TrackMetadata::from_track(&mut track).await?;
+                let metadata = TrackMetadata {
+                    title: metadata.title.or(fallback_title),
+                    requested_by: Some(author),
+                    ..metadata
+                };
+                Ok::<_, ParakeetError>((track, metadata))
+            }
+        });
+    // `join_all` keeps the order of the lookups, not the order they finish in.
+    let prepared = join_all(lookups).await;
+
+    let mut enqueued = Enqueued {
+        added: Vec::with_capacity(prepared.len()),
+        failed: Vec::new(),
+        overflow,
+        limit,
     };
+    let mut full = false;
+    for (index, lookup) in prepared.into_iter().enumerate() {
+        match lookup {
+            Err(e) => enqueued.failed.push((index, e)),
+            // Something else may have been queued during the lookups.
+            Ok(_) if full || queue_meta.len().await >= limit => {
+                full = true;
+                enqueued.overflow += 1;
+            }
+            Ok((track, metadata)) => {
+                let handle = enqueue_with_meta(call, &queue_meta, track, metadata).await;
+                enqueued.added.push(handle);
+            }
+        }
+    }
+    Ok(enqueued)
+}
 
-    let metadata = TrackMetadata::from_input(&mut input).await?;
+/// How many tracks the guild's queue can hold, preferring the guild's override over the config.
+pub async fn max_queue_len(ctx: &Context<'_>) -> Result<usize, ParakeetError> {
+    let guild_data = ctx.guild_data().await?;
+    let guild_data = guild_data.lock().await;
+    Ok(guild_data
+        .max_queue_len
+        .unwrap_or(ctx.data().config.max_queue_len()))
+}
 
+/// Add a [Track] and its [TrackMetadata] to the back of the queue.
+/// Both queues are updated while holding the call lock, so they can't be seen out of sync.
+pub async fn enqueue_with_meta(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    track: Track,
+    metadata: TrackMetadata,
+) -> TrackHandle {
+    let mut call = call.lock().await;
     queue_meta.push_back(metadata).await;
+    call.enqueue(track).await
+}
+
+/// Stop the current track and dequeue the `count - 1` tracks after it, clamped to the queue.
+/// Returns the metadata of the skipped tracks, and of the track that plays next if there is one.
+pub async fn skip(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    count: usize,
+) -> Result<(Vec<TrackMetadata>, Option<TrackMetadata>), ParakeetError> {
+    let call = call.lock().await;
+    let handle = call.queue().current().ok_or(UserError::EmptyQueue)?;
+    let skipped = apply_transaction(&call, queue_meta, |tracks| {
+        let current = tracks.front().cloned().ok_or(UserError::EmptyQueue)?;
+        let count = count.clamp(1, tracks.len());
+        let mut skipped = vec![current];
+        skipped.extend(tracks.drain(1..count));
+        Ok((skipped, tracks.get(1).cloned()))
+    })
+    .await?;
+    // The current track is removed once it ends, see [remove_finished].
+    handle.stop()?;
+    Ok(skipped)
+}
+
+/// Dequeue every track between the current one and `position`, then stop the current track so
+/// the track at `position` plays next, returning its metadata.
+pub async fn skip_to(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    position: usize,
+) -> Result<TrackMetadata, ParakeetError> {
+    let call = call.lock().await;
+    let handle = call.queue().current().ok_or(UserError::EmptyQueue)?;
+    let target = apply_transaction(&call, queue_meta, |tracks| {
+        let target = tracks
+            .get(position)
+            .filter(|_| position > 0)
+            .cloned()
+            .ok_or(UserError::BadArgs {
+                input: Some(position.to_string()),
+            })?;
+        tracks.drain(1..position);
+        Ok(target)
+    })
+    .await?;
+    // The current track is removed once it ends, see [remove_finished].
+    handle.stop()?;
+    Ok(target)
+}
+
+/// Set the volume of every queued track, where `1.0` is unchanged.
+/// Tracks enqueued later get the guild's stored volume instead, see [enqueue_known].
+pub async fn set_volume(call: &CallRef, volume: f32) -> Result<(), ParakeetError> {
+    let call = call.lock().await;
+    for handle in call.queue().current_queue() {
+        handle.set_volume(volume)?;
+    }
+    Ok(())
+}
+
+/// Make every queued track loop forever in [LoopMode::Track], or stop looping otherwise.
+/// Tracks only loop once they're current, so the rest of the queue is ready if it's skipped.
+pub async fn set_loop_mode(call: &CallRef, loop_mode: LoopMode) -> Result<(), ParakeetError> {
+    let call = call.lock().await;
+    for handle in call.queue().current_queue() {
+        match loop_mode {
+            LoopMode::Track => handle.enable_loop()?,
+            LoopMode::Off | LoopMode::Queue => handle.disable_loop()?,
+        }
+    }
+    Ok(())
+}
+
+/// Set the volume of just the current track, where `1.0` is unchanged.
+pub async fn set_current_volume(call: &CallRef, volume: f32) -> Result<(), ParakeetError> {
+    let call = call.lock().await;
+    let handle = call.queue().current().ok_or(UserError::EmptyQueue)?;
+    handle.set_volume(volume)?;
+    Ok(())
+}
+
+/// Pause `handle` if it's the current track, so a queue that was just started waits for [resume].
+pub async fn hold_if_current(call: &CallRef, handle: &TrackHandle) -> Result<(), ParakeetError> {
+    let call = call.lock().await;
+    let is_current = call
+        .queue()
+        .current()
+        .is_some_and(|current| current.uuid() == handle.uuid());
+    if is_current {
+        handle.pause()?;
+    }
+    Ok(())
+}
+
+/// Jump to `position` in the current track, returning where it ended up.
+/// Positions past the track's known duration are rejected.
+pub async fn seek(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    position: Duration,
+) -> Result<Duration, ParakeetError> {
+    let handle = {
+        let call = call.lock().await;
+        let handle = call.queue().current().ok_or(UserError::EmptyQueue)?;
+        let meta = queue_meta.front().await.ok_or(UserError::EmptyQueue)?;
+        if meta.duration.is_some_and(|duration| position >= duration) {
+            Err(UserError::BadArgs {
+                input: Some(lib::format_duration(&position)),
+            })?
+        }
+        handle
+    };
+    // Seeking back may re-create the input, so the call isn't kept locked meanwhile.
+    Ok(handle.seek_async(position).await?)
+}
+
+/// Resume the current track, returning its metadata.
+pub async fn resume(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+) -> Result<TrackMetadata, ParakeetError> {
+    let call = call.lock().await;
+    let meta = queue_meta.front().await.ok_or(UserError::EmptyQueue)?;
+    call.queue().resume()?;
+    Ok(meta)
+}
+
+/// Remove the track with the given [TrackMetadata::id] from the queue, returning its metadata.
+/// If it's the current track, it's skipped instead.
+pub async fn remove(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    id: Uuid,
+) -> Result<TrackMetadata, ParakeetError> {
+    let call = call.lock().await;
+    let queue = call.queue();
+
+    let is_current = queue.current().is_some_and(|handle| handle.uuid() == id);
+    if is_current {
+        let meta = queue_meta.front().await.ok_or(UserError::EmptyQueue)?;
+        queue.skip()?;
+        return Ok(meta);
+    }
+
+    apply_transaction(&call, queue_meta, |tracks| {
+        let removed = tracks
+            .iter()
+            .position(|track| track.id == id)
+            .and_then(|index| tracks.remove(index));
+        Ok(removed.ok_or(UserError::BadArgs { input: None })?)
+    })
+    .await
+}
+
+/// Remove every track with the same [url](TrackMetadata::url) as an earlier one, returning how
+/// many were removed. The first of each is kept, so the current track is never removed.
+/// Tracks without an url are never duplicates.
+pub async fn remove_duplicates(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+) -> Result<usize, ParakeetError> {
+    transaction(call, queue_meta, |tracks| {
+        let before = tracks.len();
+        let mut seen = HashSet::new();
+        tracks.retain(|track| match &track.url {
+            Some(url) => seen.insert(url.clone()),
+            None => true,
+        });
+        Ok(before - tracks.len())
+    })
+    .await
+}
+
+/// Remove every track with the same [url](TrackMetadata::url) as the one right before it,
+/// returning how many were removed. The first of each run is kept, so the current track is
+/// never removed.
+pub async fn remove_consecutive_duplicates(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+) -> Result<usize, ParakeetError> {
+    transaction(call, queue_meta, |tracks| {
+        let repeats: HashSet<Uuid> = consecutive_duplicates(tracks.make_contiguous())
+            .into_iter()
+            .collect();
+        tracks.retain(|track| !repeats.contains(&track.id));
+        Ok(repeats.len())
+    })
+    .await
+}
 
-    let track_handle = {
-        let mut call = call.lock().await;
-        call.enqueue_input(input).await
+/// Move the queued track at index `from` to index `to`, returning its metadata.
+/// The current track (index 0) can't be moved, nor can anything be moved in front of it.
+pub async fn move_track(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    from: usize,
+    to: usize,
+) -> Result<TrackMetadata, ParakeetError> {
+    let bad_index = |index: usize| UserError::BadArgs {
+        input: Some(index.to_string()),
     };
+    if from == 0 {
+        Err(bad_index(from))?
+    }
+    if to == 0 {
+        Err(bad_index(to))?
+    }
+
+    transaction(call, queue_meta, |tracks| {
+        let meta = tracks.get(from).cloned().ok_or(bad_index(from))?;
+        if !lib::move_in_deque(tracks, from, to) {
+            Err(bad_index(to))?
+        }
+        Ok(meta)
+    })
+    .await
+}
+
+/// Keep only the first `len` tracks in both queues, returning the metadata of the removed ones.
+/// The current track is always kept.
+pub async fn truncate(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    len: usize,
+) -> Result<Vec<TrackMetadata>, ParakeetError> {
+    transaction(call, queue_meta, |tracks| {
+        let len = len.max(1).min(tracks.len());
+        Ok(tracks.split_off(len).into())
+    })
+    .await
+}
+
+/// Shuffle everything after the current track, in both queues the same way.
+/// Returns how many tracks were shuffled, nothing happens if there are fewer than two.
+pub async fn shuffle(call: &CallRef, queue_meta: &QueueMeta) -> Result<usize, ParakeetError> {
+    shuffle_from(call, queue_meta, 1).await
+}
+
+/// Shuffle the tracks from index `start` on, see [shuffle].
+/// The current track (index 0) always stays first.
+async fn shuffle_from(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    start: usize,
+) -> Result<usize, ParakeetError> {
+    let start = start.max(1);
+    transaction(call, queue_meta, |tracks| {
+        let shuffled = tracks.len().saturating_sub(start);
+        if shuffled < 2 {
+            return Ok(0);
+        }
+
+        let seed = rand::random();
+        tracing::debug!("Shuffling {shuffled} tracks from {start} with seed {seed}");
+        lib::permute_deque(tracks, &lib::shuffled_order(tracks.len(), start, seed));
+        Ok(shuffled)
+    })
+    .await
+}
+
+/// Shuffle just the tracks in `ids` among the positions they're at, e.g. ones that were just added.
+/// Everything else stays where it is, and so does the current track even if it's in `ids`.
+/// Returns how many tracks were shuffled, nothing happens if there are fewer than two.
+pub async fn shuffle_ids(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    ids: &HashSet<Uuid>,
+) -> Result<usize, ParakeetError> {
+    transaction(call, queue_meta, |tracks| {
+        let positions: Vec<usize> = (1..tracks.len())
+            .filter(|&index| ids.contains(&tracks[index].id))
+            .collect();
+        if positions.len() < 2 {
+            return Ok(0);
+        }
+
+        let seed = rand::random();
+        tracing::debug!(
+            "Shuffling {} added tracks with seed {seed}",
+            positions.len()
+        );
+        let picked: Vec<TrackMetadata> = positions.iter().map(|&i| tracks[i].clone()).collect();
+        let order = lib::shuffled_order(positions.len(), 0, seed);
+        for (&position, &from) in positions.iter().zip(&order) {
+            tracks[position] = picked[from].clone();
+        }
+        Ok(positions.len())
+    })
+    .await
+}
+
+/// Change the queue by applying `mutate` to a copy of its metadata, then make both queues match it.
+///
+/// `mutate` may reorder and remove tracks but not add any, and the current track has to stay
+/// first (use [skip] to get rid of it). Removed tracks are stopped.
+///
+/// # Consistency
+/// Both queues are checked to be in sync beforehand, and everything happens while holding the
+/// call lock, so nothing else can change either queue in between.
+/// Nothing is changed unless `mutate` succeeds and its result follows the rules above,
+/// otherwise both queues end up in the same order.
+pub async fn transaction<T>(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    mutate: impl FnOnce(&mut VecDeque<TrackMetadata>) -> Result<T, ParakeetError>,
+) -> Result<T, ParakeetError> {
+    let call = call.lock().await;
+    apply_transaction(&call, queue_meta, mutate).await
+}
+
+/// [transaction] for when the call is already locked.
+async fn apply_transaction<T>(
+    call: &songbird::Call,
+    queue_meta: &QueueMeta,
+    mutate: impl FnOnce(&mut VecDeque<TrackMetadata>) -> Result<T, ParakeetError>,
+) -> Result<T, ParakeetError> {
+    let rollback = |reason: &str| ParakeetError::QueueTransaction {
+        reason: reason.to_string(),
+    };
+    let queue = call.queue();
+
+    let track_ids: Vec<Uuid> = queue.current_queue().iter().map(|h| h.uuid()).collect();
+    // Songbird pops a finished track before [remove_finished] drops its metadata.
+    // That metadata is left out of the transaction, but kept so it can still be removed.
+    let (finished, before): (Vec<_>, Vec<_>) = queue_meta
+        .snapshot()
+        .await
+        .into_iter()
+        .partition(|meta| !track_ids.contains(&meta.id));
+    if before
+        .iter()
+        .map(|meta| meta.id)
+        .ne(track_ids.iter().copied())
+    {
+        Err(rollback("the queues were already out of sync"))?
+    }
+
+    let mut after = VecDeque::from(before);
+    let value = mutate(&mut after)?;
+
+    // Check the result before touching either queue.
+    let mut remaining: HashSet<Uuid> = track_ids.iter().copied().collect();
+    if !after.iter().all(|meta| remaining.remove(&meta.id)) {
+        Err(rollback("tracks were added or duplicated"))?
+    }
+    if track_ids.first() != after.front().map(|meta| &meta.id) {
+        Err(rollback("the current track was moved"))?
+    }
+
+    let order: Vec<Uuid> = after.iter().map(|meta| meta.id).collect();
+    queue_meta
+        .replace_all(finished.into_iter().chain(after).collect())
+        .await;
+    let removed = queue.modify_queue(|tracks| {
+        let mut by_id: HashMap<Uuid, _> = tracks.drain(..).map(|t| (t.uuid(), t)).collect();
+        tracks.extend(order.iter().filter_map(|id| by_id.remove(id)));
+        by_id.into_values().collect::<Vec<_>>()
+    });
+    // Dequeued tracks still hold on to their input until stopped.
+    for track in removed {
+        let _ = track.stop();
+    }
+
+    Ok(value)
+}
+
+/// Play [Input] right away, alongside the queue instead of in it.
+/// These tracks are ignored by [remove_finished] when they end.
+pub async fn play_transient(call: &CallRef, input: Input) -> TrackHandle {
+    let mut call = call.lock().await;
+    let handle = call.play_input(input);
+    // Marked while holding the call lock, so `remove_finished` always sees the mark.
+    handle.typemap().write().await.insert::<TransientKey>(());
+    handle
+}
+
+/// Whether `handle` was played by [play_transient].
+pub async fn is_transient(handle: &TrackHandle) -> bool {
+    handle.typemap().read().await.contains_key::<TransientKey>()
+}
+
+/// Remove the metadata of a track that finished playing.
+/// Does nothing if `ended` was played by [play_transient] or was already removed.
+pub async fn remove_finished(
+    call: &CallRef,
+    queue_meta: &QueueMeta,
+    ended: &TrackHandle,
+) -> Option<TrackMetadata> {
+    // Only held to keep the lock order, see the module docs.
+    let _call = call.lock().await;
+    if is_transient(ended).await {
+        return None;
+    }
+    queue_meta.remove_by_id(ended.uuid()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Not valid audio, but enough to fill songbird's queue.
+    static SILENCE: &[u8] = &[0; 64];
+
+    /// A call that's never connected, so its tracks don't play.
+    fn test_call() -> CallRef {
+        let id = NonZeroU64::new(1).unwrap();
+        Arc::new(Mutex::new(songbird::Call::standalone(id, id)))
+    }
+
+    /// Check that the call's queue and `queue_meta` hold the same tracks in the same order,
+    /// returning their ids.
+    async fn assert_aligned(call: &CallRef, queue_meta: &QueueMeta) -> Vec<Uuid> {
+        let meta_ids: Vec<Uuid> = queue_meta.snapshot().await.iter().map(|m| m.id).collect();
+        let track_ids: Vec<Uuid> = call
+            .lock()
+            .await
+            .queue()
+            .current_queue()
+            .iter()
+            .map(|handle| handle.uuid())
+            .collect();
+        assert_eq!(track_ids, meta_ids);
+        meta_ids
+    }
+
+    /// A placeholder track and its metadata for the test queue.
+    fn test_track() -> (Track, TrackMetadata) {
+        let track = Track::new(SILENCE.into());
+        let meta = TrackMetadata {
+            id: track.uuid,
+            title: None,
+            duration: None,
+            channel: None,
+            thumbnail_url: None,
+            url: None,
+            requested_by: None,
+        };
+        (track, meta)
+    }
+
+    /// A [test call](test_call) and its metadata, with `len` [placeholder tracks](test_track) queued.
+    async fn filled_queue(len: usize) -> (CallRef, QueueMeta) {
+        let call = test_call();
+        let queue_meta = QueueMeta::default();
+        for _ in 0..len {
+            let (track, meta) = test_track();
+            enqueue_with_meta(&call, &queue_meta, track, meta).await;
+        }
+        (call, queue_meta)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_enqueue_and_skip_dont_deadlock() {
+        let call = test_call();
+        let queue_meta = QueueMeta::default();
+        let (track, meta) = test_track();
+        let ended = enqueue_with_meta(&call, &queue_meta, track, meta).await;
+
+        let mut tasks = Vec::new();
+        for i in 0..100 {
+            let call = call.clone();
+            let queue_meta = queue_meta.clone();
+            let ended = ended.clone();
+            tasks.push(tokio::spawn(async move {
+                match i % 3 {
+                    0 => {
+                        let (track, meta) = test_track();
+                        enqueue_with_meta(&call, &queue_meta, track, meta).await;
+                    }
+                    1 => {
+                        let _ = skip(&call, &queue_meta, 1).await;
+                    }
+                    _ => {
+                        remove_finished(&call, &queue_meta, &ended).await;
+                    }
+                }
+            }));
+        }
+
+        tokio::time::timeout(Duration::from_secs(10), join_all(tasks))
+            .await
+            .expect("enqueue and skip deadlocked");
+    }
+
+    #[tokio::test]
+    async fn shuffle_keeps_queues_aligned() {
+        let (call, queue_meta) = filled_queue(10).await;
+        let before = queue_meta.snapshot().await;
+
+        assert_eq!(shuffle(&call, &queue_meta).await.unwrap(), 9);
+
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        assert_eq!(meta_ids[0], before[0].id);
+    }
+
+    #[tokio::test]
+    async fn skip_many_keeps_queues_aligned() {
+        let (call, queue_meta) = filled_queue(5).await;
+        let before = queue_meta.snapshot().await;
+
+        let (skipped, next) = skip(&call, &queue_meta, 3).await.unwrap();
+        let skipped_ids: Vec<Uuid> = skipped.iter().map(|meta| meta.id).collect();
+        assert_eq!(skipped_ids, [before[0].id, before[1].id, before[2].id]);
+        assert_eq!(next.map(|meta| meta.id), Some(before[3].id));
+
+        // Without a driver the stopped track never ends, so it's still first.
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        assert_eq!(meta_ids, [before[0].id, before[3].id, before[4].id]);
+
+        // More than the queue holds skips all of it.
+        let (skipped, next) = skip(&call, &queue_meta, 100).await.unwrap();
+        assert_eq!(skipped.len(), 3);
+        assert!(next.is_none());
+    }
+
+    #[tokio::test]
+    async fn skip_to_keeps_queues_aligned() {
+        let (call, queue_meta) = filled_queue(5).await;
+        let before = queue_meta.snapshot().await;
+
+        // The current track and out of range positions can't be jumped to.
+        assert!(skip_to(&call, &queue_meta, 0).await.is_err());
+        assert!(skip_to(&call, &queue_meta, 5).await.is_err());
+
+        let target = skip_to(&call, &queue_meta, 3).await.unwrap();
+        assert_eq!(target.id, before[3].id);
+
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        assert_eq!(meta_ids, [before[0].id, before[3].id, before[4].id]);
+    }
+
+    #[tokio::test]
+    async fn duplicates_are_removed_after_the_first() {
+        let call = test_call();
+        let queue_meta = QueueMeta::default();
+        for url in ["a", "b", "a", "c", "b", "a"] {
+            let (track, mut meta) = test_track();
+            meta.url = Some(url.to_string());
+            enqueue_with_meta(&call, &queue_meta, track, meta).await;
+        }
+        for _ in 0..2 {
+            let (track, meta) = test_track();
+            enqueue_with_meta(&call, &queue_meta, track, meta).await;
+        }
+        let before = queue_meta.snapshot().await;
+
+        assert_eq!(remove_duplicates(&call, &queue_meta).await.unwrap(), 3);
+        assert_eq!(remove_duplicates(&call, &queue_meta).await.unwrap(), 0);
+
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        let expected: Vec<Uuid> = [0, 1, 3, 6, 7].iter().map(|&i| before[i].id).collect();
+        assert_eq!(meta_ids, expected);
+    }
+
+    #[tokio::test]
+    async fn repeats_in_a_row_are_removed() {
+        let call = test_call();
+        let queue_meta = QueueMeta::default();
+        for url in ["a", "a", "b", "a", "b", "b", "b"] {
+            let (track, mut meta) = test_track();
+            meta.url = Some(url.to_string());
+            enqueue_with_meta(&call, &queue_meta, track, meta).await;
+        }
+        let before = queue_meta.snapshot().await;
+
+        assert_eq!(
+            remove_consecutive_duplicates(&call, &queue_meta)
+                .await
+                .unwrap(),
+            3
+        );
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        let expected: Vec<Uuid> = [0, 2, 3, 4].iter().map(|&i| before[i].id).collect();
+        assert_eq!(meta_ids, expected);
+    }
+
+    #[tokio::test]
+    async fn shuffle_ids_leaves_other_tracks() {
+        let (call, queue_meta) = filled_queue(8).await;
+        let before: Vec<Uuid> = queue_meta.snapshot().await.iter().map(|m| m.id).collect();
+
+        // The current track is never moved, even if it's one of them.
+        let ids: HashSet<Uuid> = [0, 2, 3, 5, 6].iter().map(|&i| before[i]).collect();
+        assert_eq!(shuffle_ids(&call, &queue_meta, &ids).await.unwrap(), 4);
+
+        let after = assert_aligned(&call, &queue_meta).await;
+        for kept in [0, 1, 4, 7] {
+            assert_eq!(after[kept], before[kept]);
+        }
+        let shuffled: HashSet<Uuid> = [2, 3, 5, 6].iter().map(|&i| after[i]).collect();
+        assert_eq!(shuffled, &ids - &HashSet::from([before[0]]));
+    }
+
+    #[tokio::test]
+    async fn move_keeps_queues_aligned() {
+        let (call, queue_meta) = filled_queue(5).await;
+        let before = queue_meta.snapshot().await;
+
+        let moved = move_track(&call, &queue_meta, 4, 1).await.unwrap();
+        assert_eq!(moved.id, before[4].id);
+
+        // The current track and out of range positions can't be moved.
+        assert!(move_track(&call, &queue_meta, 0, 2).await.is_err());
+        assert!(move_track(&call, &queue_meta, 2, 0).await.is_err());
+        assert!(move_track(&call, &queue_meta, 1, 5).await.is_err());
+
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        let expected: Vec<Uuid> = [0, 4, 1, 2, 3].iter().map(|&i| before[i].id).collect();
+        assert_eq!(meta_ids, expected);
+    }
+
+    #[tokio::test]
+    async fn finished_tracks_are_left_out_of_transactions() {
+        let (call, queue_meta) = filled_queue(4).await;
+        let before = queue_meta.snapshot().await;
+        // Like songbird does once a track ends, before its metadata is removed.
+        let ended = call.lock().await.queue().dequeue(0).unwrap();
+
+        let len = transaction(&call, &queue_meta, |tracks| {
+            tracks.swap(1, 2);
+            Ok(tracks.len())
+        });
+        assert_eq!(len.await.unwrap(), 3);
+
+        let removed = remove_finished(&call, &queue_meta, &ended.handle()).await;
+        assert_eq!(removed.map(|meta| meta.id), Some(before[0].id));
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        assert_eq!(meta_ids, [before[1].id, before[3].id, before[2].id]);
+    }
+
+    #[tokio::test]
+    async fn front_tracks_play_next() {
+        let call = test_call();
+        let queue_meta = QueueMeta::default();
+        let (track, meta) = test_track();
+        let first = enqueue_front_with_meta(&call, &queue_meta, track, meta).await;
+        for _ in 0..3 {
+            let (track, meta) = test_track();
+            enqueue_with_meta(&call, &queue_meta, track, meta).await;
+        }
+        let before = queue_meta.snapshot().await;
+        let (track, meta) = test_track();
+        let next = enqueue_front_with_meta(&call, &queue_meta, track, meta).await;
+
+        let meta_ids = assert_aligned(&call, &queue_meta).await;
+        assert_eq!(meta_ids[0], first.uuid());
+        assert_eq!(meta_ids[1], next.uuid());
+        assert_eq!(
+            meta_ids[2..],
+            before[1..].iter().map(|m| m.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn truncate_keeps_queues_aligned() {
+        let (call, queue_meta) = filled_queue(5).await;
+        let before = queue_meta.snapshot().await;
+        // Its metadata is still around, like right after a track ends.
+        let ended = call.lock().await.queue().dequeue(0).unwrap();
+
+        let removed = truncate(&call, &queue_meta, 2).await.unwrap();
+        let removed_ids: Vec<Uuid> = removed.iter().map(|meta| meta.id).collect();
+        assert_eq!(removed_ids, [before[3].id, before[4].id]);
+
+        // The current track is kept even when asked for none.
+        assert_eq!(truncate(&call, &queue_meta, 0).await.unwrap().len(), 1);
+        remove_finished(&call, &queue_meta, &ended.handle()).await;
+        assert_eq!(assert_aligned(&call, &queue_meta).await, [before[1].id]);
+    }
+
+    #[test]
+    fn recognizes_attachments() {
+        assert!(is_attachment(
+            "https://cdn.discordapp.com/attachments/1/2/song.mp3?ex=abc"
+        ));
+        assert!(is_attachment(
+            "https://media.discordapp.net/attachments/1/2/song.ogg"
+        ));
+        assert!(!is_attachment("https://cdn.discordapp.com/avatars/1/2.png"));
+        assert!(!is_attachment("https://www.youtube.com/watch?v=abc"));
+        assert!(!is_attachment("not a url"));
+    }
+
+    #[test]
+    fn rebuilt_tracks_loop_in_track_mode() {
+        let (_, mut meta) = test_track();
+        meta.url = Some("https://example.com/song".to_string());
+        let http_client = reqwest::Client::new();
+        for (loop_mode, loops) in [
+            (LoopMode::Off, LoopState::Finite(0)),
+            (LoopMode::Queue, LoopState::Finite(0)),
+            (LoopMode::Track, LoopState::Infinite),
+        ] {
+            let rebuilt = rebuild_tracks(http_client.clone(), 1.0, loop_mode, vec![meta.clone()]);
+            assert_eq!(rebuilt[0].0.loops, loops);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_transactions_change_nothing() {
+        let (call, queue_meta) = filled_queue(4).await;
+        let before = assert_aligned(&call, &queue_meta).await;
+
+        // Fails after changing the copy.
+        let failed = transaction(&call, &queue_meta, |tracks| {
+            tracks.pop_back();
+            Err::<(), _>(UserError::EmptyQueue.into())
+        });
+        assert!(failed.await.is_err());
+        // Moves the current track.
+        let moved = transaction(&call, &queue_meta, |tracks| {
+            tracks.swap(0, 1);
+            Ok(())
+        });
+        assert!(moved.await.is_err());
+        // Duplicates a track.
+        let duplicated = transaction(&call, &queue_meta, |tracks| {
+            tracks.push_back(tracks[1].clone());
+            Ok(())
+        });
+        assert!(duplicated.await.is_err());
 
-    Ok(track_handle)
+        assert_eq!(assert_aligned(&call, &queue_meta).await, before);
+    }
 }
@@ -0,0 +1,177 @@
+//! Estimates of when queued tracks play, see [Eta].
+
+use std::fmt::Display;
+use std::time::Duration;
+
+use crate::data::LoopMode;
+use crate::data::TrackMetadata;
+use crate::lib;
+use crate::serenity::UserId;
+
+/// When something in the queue happens.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Eta {
+    /// It's happening right now.
+    Now,
+    /// In about this long, from the durations that are known.
+    In(Duration),
+    /// Never, because of the [LoopMode].
+    Never(LoopMode),
+}
+
+impl Display for Eta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Eta::Now => write!(f, "now"),
+            Eta::In(dur) => write!(f, "~{}", lib::format_duration(dur)),
+            Eta::Never(LoopMode::Track) => write!(f, "∞ (track loop active)"),
+            Eta::Never(_) => write!(f, "∞ (queue loop active)"),
+        }
+    }
+}
+
+/// When the queue `tracks` runs out, if the current track is `elapsed` in.
+pub fn until_empty(tracks: &[TrackMetadata], elapsed: Duration, loop_mode: LoopMode) -> Eta {
+    match loop_mode {
+        LoopMode::Off if tracks.is_empty() => Eta::Now,
+        LoopMode::Off => Eta::In(TrackMetadata::total_duration(tracks).saturating_sub(elapsed)),
+        // Neither loop ever runs out.
+        mode => Eta::Never(mode),
+    }
+}
+
+/// When the next track from `user` plays, if the current track is `elapsed` in.
+/// Returns `None` if they have nothing queued.
+pub fn until_next(
+    tracks: &[TrackMetadata],
+    elapsed: Duration,
+    loop_mode: LoopMode,
+    user: UserId,
+) -> Option<Eta> {
+    let is_theirs = |track: &TrackMetadata| track.requested_by == Some(user);
+    let index = tracks.iter().position(is_theirs)?;
+
+    let eta = match (index, loop_mode) {
+        (0, _) => Eta::Now,
+        // The current track never ends.
+        (_, LoopMode::Track) => Eta::Never(LoopMode::Track),
+        (index, _) => {
+            let ahead = TrackMetadata::total_duration(&tracks[..index]);
+            Eta::In(ahead.saturating_sub(elapsed))
+        }
+    };
+
+    // When their track is the current one, the queue loop brings it around again
+    // after everything else plays.
+    let eta = match (&eta, loop_mode, tracks.get(1..)) {
+        (Eta::Now, LoopMode::Queue, Some(rest)) if !rest.is_empty() => {
+            let next = rest
+                .iter()
+                .position(is_theirs)
+                .map_or(tracks.len(), |i| i + 1);
+            let ahead = TrackMetadata::total_duration(&tracks[..next]);
+            Eta::In(ahead.saturating_sub(elapsed))
+        }
+        _ => eta,
+    };
+
+    Some(eta)
+}
+
+/// How many tracks from the front of `tracks` finish within `budget`, if the current track
+/// is `elapsed` in. The current track is always kept, since it's already playing.
+/// Tracks with an unknown duration might not fit, so nothing from them onwards is kept.
+pub fn fit_len(tracks: &[TrackMetadata], elapsed: Duration, budget: Duration) -> usize {
+    let Some((current, rest)) = tracks.split_first() else {
+        return 0;
+    };
+    let mut total = current.duration.unwrap_or_default().saturating_sub(elapsed);
+
+    let mut kept = 1;
+    for track in rest {
+        let Some(duration) = track.duration else {
+            break;
+        };
+        total += duration;
+        if total > budget {
+            break;
+        }
+        kept += 1;
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A track from `user` lasting `secs`.
+    fn track(secs: u64, user: u64) -> TrackMetadata {
+        TrackMetadata {
+            id: uuid::Uuid::nil(),
+            title: None,
+            duration: Some(Duration::from_secs(secs)),
+            channel: None,
+            thumbnail_url: None,
+            url: None,
+            requested_by: Some(UserId::new(user)),
+        }
+    }
+
+    #[test]
+    fn fit_is_conservative() {
+        let mins = |m: u64| Duration::from_secs(m * 60);
+        let tracks = [track(600, 1), track(600, 1), track(600, 1), track(600, 1)];
+
+        // 5 minutes left of the current track, then 10 minutes each.
+        assert_eq!(fit_len(&tracks, mins(5), mins(25)), 3);
+        assert_eq!(fit_len(&tracks, mins(5), mins(24)), 2);
+        assert_eq!(fit_len(&tracks, Duration::ZERO, mins(1)), 1);
+        assert_eq!(fit_len(&tracks, Duration::ZERO, mins(60)), 4);
+        assert_eq!(fit_len(&[], Duration::ZERO, mins(60)), 0);
+
+        let mut unknown = tracks.to_vec();
+        unknown[2].duration = None;
+        assert_eq!(fit_len(&unknown, Duration::ZERO, mins(60)), 2);
+    }
+
+    #[test]
+    fn eta_accounts_for_loops() {
+        let tracks = [track(60, 1), track(30, 2), track(20, 1)];
+        let elapsed = Duration::from_secs(10);
+        let secs = |secs| Some(Eta::In(Duration::from_secs(secs)));
+
+        assert_eq!(
+            until_empty(&tracks, elapsed, LoopMode::Off),
+            Eta::In(Duration::from_secs(100))
+        );
+        assert_eq!(
+            until_empty(&tracks, elapsed, LoopMode::Queue),
+            Eta::Never(LoopMode::Queue)
+        );
+
+        // Their track is up next once the current one ends.
+        assert_eq!(
+            until_next(&tracks, elapsed, LoopMode::Off, UserId::new(2)),
+            secs(50)
+        );
+        assert_eq!(
+            until_next(&tracks, elapsed, LoopMode::Track, UserId::new(2)),
+            Some(Eta::Never(LoopMode::Track))
+        );
+
+        // Their track is playing, and the queue loop brings their next one around.
+        assert_eq!(
+            until_next(&tracks, elapsed, LoopMode::Off, UserId::new(1)),
+            Some(Eta::Now)
+        );
+        assert_eq!(
+            until_next(&tracks, elapsed, LoopMode::Queue, UserId::new(1)),
+            secs(80)
+        );
+        assert_eq!(
+            until_next(&tracks, elapsed, LoopMode::Off, UserId::new(3)),
+            None
+        );
+    }
+}
@@ -1,23 +1,44 @@
 //! Event handling
 
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::time::Duration;
 
 use async_trait::async_trait;
+use songbird::tracks::PlayMode;
+use songbird::tracks::TrackHandle;
 use songbird::CoreEvent;
 use songbird::Event;
 use songbird::EventContext;
 use songbird::EventHandler;
 use songbird::TrackEvent;
 
+use super::call::enqueue_with_meta;
 use super::call::get_manager;
+use super::call::is_transient;
+use super::call::rebuild_tracks;
+use super::call::remove_finished;
 use super::call::CallRef;
+use crate::data::missing_title;
 use crate::data::GetData;
+use crate::data::GuildDataRef;
+use crate::data::LoopMode;
 use crate::data::QueueMeta;
 use crate::error::UserError;
+use crate::lib;
+use crate::lib::metrics::METRICS;
 use crate::serenity;
 use crate::Context;
 use crate::ParakeetError;
 
+/// Tracks that started playing longer ago than this aren't announced, see [AnnounceTrack].
+const ANNOUNCE_WITHIN: Duration = Duration::from_secs(1);
+
+/// How many tracks in a row can end right away before the queue is paused, see [RemoveMeta].
+const FAST_END_LIMIT: usize = 3;
+
 /// Initialize global events.
 /// Only initializes if a [songbird::Call] hasn't been initialized yet.
 pub async fn init_global_events(ctx: &Context<'_>) -> Result<CallRef, ParakeetError> {
@@ -33,14 +54,19 @@ pub async fn init_global_events(ctx: &Context<'_>) -> Result<CallRef, ParakeetEr
                 tracing::info!("Initializing global events.");
 
                 // Create the events.
-                let idle_event = CheckIdle::new(&call, ctx);
-                let dc_event = DisconnectStop::new(&call);
+                let idle_event = CheckIdle::new(&call, ctx).await?;
+                let dc_event = DisconnectStop::new(&call, ctx).await?;
                 let end_event = RemoveMeta::new(&call, ctx).await?;
+                let connection_event = CountConnection::default();
 
                 // Register them as global events.
                 idle_event.register(Duration::from_secs(300)).await;
                 dc_event.register().await;
                 end_event.register().await;
+                connection_event.register(&call).await;
+                if ctx.data().config.announce_tracks() {
+                    AnnounceTrack::new(&call, ctx).await?.register().await;
+                }
                 call
             }
         }
@@ -49,20 +75,62 @@ pub async fn init_global_events(ctx: &Context<'_>) -> Result<CallRef, ParakeetEr
 }
 
 /// Check if there are non-bot users in the call, if not then disconnect.
+/// If configured, also waits for the queue to be empty before disconnecting,
+/// and disconnects when nothing has played for too long even with members present.
 struct CheckIdle {
     /// The call to check.
     call: CallRef,
     /// Needed to find channels and guilds.
     ctx: serenity::Context,
+    /// The queue to check if `requires_empty_queue` is set.
+    queue_meta: QueueMeta,
+    /// Reference to the guild's data, to check when playback last progressed.
+    guild_data: GuildDataRef,
+    /// Don't disconnect while something is queued.
+    requires_empty_queue: bool,
+    /// Disconnect after playback hasn't progressed for this long, see [Config::paused_timeout](crate::Config::paused_timeout).
+    paused_timeout: Option<Duration>,
 }
 
 impl CheckIdle {
     /// Constructor for [CheckIdle]
-    fn new(call: &CallRef, ctx: &Context<'_>) -> Self {
+    async fn new(call: &CallRef, ctx: &Context<'_>) -> Result<Self, ParakeetError> {
+        let requires_empty_queue = ctx.data().config.idle_requires_empty_queue();
+        let paused_timeout = ctx.data().config.paused_timeout();
+        let queue_meta = ctx.queue_meta().await?;
+        let guild_data = ctx.guild_data().await?;
         // Should be cheap to clone
         let ctx = ctx.serenity_context().clone();
         let call = call.clone();
-        Self { call, ctx }
+        Ok(Self {
+            call,
+            ctx,
+            queue_meta,
+            guild_data,
+            requires_empty_queue,
+            paused_timeout,
+        })
+    }
+
+    /// Whether nothing has played for longer than the `paused_timeout`.
+    /// A track that's still playing counts as progress.
+    async fn paused_too_long(&self, call: &songbird::Call) -> bool {
+        let Some(timeout) = self.paused_timeout else {
+            return false;
+        };
+        let playing = match call.queue().current() {
+            Some(handle) => handle
+                .get_info()
+                .await
+                .is_ok_and(|info| info.playing == PlayMode::Play),
+            None => false,
+        };
+
+        let mut guild_data = self.guild_data.lock().await;
+        if playing {
+            guild_data.mark_progress();
+        }
+        guild_data.paused_for(timeout)
     }
 
     /// Register this as a global event
@@ -94,7 +162,16 @@ impl EventHandler for CheckIdle {
             let has_members = members.iter().any(|m| !m.user.bot);
 
             if has_members {
-                // With members, do nothing and retry on next trigger.
+                if !self.paused_too_long(&call).await {
+                    // With members, do nothing and retry on next trigger.
+                    return None;
+                }
+                tracing::info!("Paused for too long! Disconnecting from voice channel.");
+                call.leave().await.ok()?;
+                None
+            } else if self.requires_empty_queue && !self.queue_meta.is_empty().await {
+                // Still playing to an empty room, retry on next trigger.
+                tracing::debug!("Alone but the queue isn't empty, staying.");
                 None
             } else {
                 // Otherwise, leave the call and cancel this handler.
@@ -114,18 +191,23 @@ impl EventHandler for CheckIdle {
 /// 'Stopping' means:
 /// - End anything currently playing.
 /// - Reset the queue.
-/// - Reset [QueueMeta]
+/// - Mark [QueueMeta] as [stale](crate::data::GuildData::stale_queue), it's resumed or cleared on rejoin.
 /// - Remove other global events.
+/// - Reset [SessionStats](crate::data::SessionStats).
+/// - Abort the guild's [tasks](crate::data::GuildTasks).
 struct DisconnectStop {
     /// Reference to the call that will be dropped.
     call: CallRef,
+    /// Reference to the guild's data.
+    guild_data: GuildDataRef,
 }
 
 impl DisconnectStop {
     /// Constructor for [DisconnectStop]
-    fn new(call: &CallRef) -> Self {
+    async fn new(call: &CallRef, ctx: &Context<'_>) -> Result<Self, ParakeetError> {
         let call = call.clone();
-        Self { call }
+        let guild_data = ctx.guild_data().await?;
+        Ok(Self { call, guild_data })
     }
 
     /// Register this as a global event.
@@ -143,28 +225,278 @@ impl EventHandler for DisconnectStop {
         tracing::info!("Stopping on disconnect!");
         let call_lock = self.call.lock().await;
         call_lock.queue().stop();
+
+        let mut guild_data = self.guild_data.lock().await;
+        guild_data.session = Default::default();
+        guild_data.stale_queue = true;
+
+        let aborted = guild_data.tasks.abort_all();
+        if !aborted.is_empty() {
+            tracing::info!(
+                "Aborted {} leftover task(s) on disconnect: {}",
+                aborted.len(),
+                aborted.join(", ")
+            );
+        }
+        None
+    }
+}
+
+/// Post the track that just started to the channel tracks were last added from,
+/// see [GuildData::announce_channel](crate::data::GuildData::announce_channel).
+struct AnnounceTrack {
+    /// Reference to call.
+    call: CallRef,
+    /// Reference to queue metadata.
+    queue_meta: QueueMeta,
+    /// Reference to the guild's data.
+    guild_data: GuildDataRef,
+    /// Used to send the announcements.
+    http: Arc<serenity::Http>,
+}
+
+impl AnnounceTrack {
+    /// Constructor for [AnnounceTrack]
+    async fn new(call: &CallRef, ctx: &Context<'_>) -> Result<Self, ParakeetError> {
+        Ok(Self {
+            call: call.clone(),
+            queue_meta: ctx.queue_meta().await?,
+            guild_data: ctx.guild_data().await?,
+            http: ctx.serenity_context().http.clone(),
+        })
+    }
+
+    /// Register this as a global event
+    async fn register(self) {
+        tracing::debug!("Registering announce track global event.");
+        let call = self.call.clone();
+        let mut call = call.lock().await;
+        call.add_global_event(Event::Track(TrackEvent::Play), self);
+    }
+}
+
+#[async_trait]
+impl EventHandler for AnnounceTrack {
+    async fn act(&self, ectx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ectx else {
+            return None;
+        };
+
+        for (state, handle) in tracks.iter() {
+            // Resuming from a pause also counts as playing, but isn't worth announcing.
+            if state.play_time > ANNOUNCE_WITHIN || is_transient(handle).await {
+                continue;
+            }
+            let Some(channel_id) = self.guild_data.lock().await.announce_channel else {
+                continue;
+            };
+            let Some(meta) = self.queue_meta.get_by_id(handle.uuid()).await else {
+                continue;
+            };
+
+            let title = match meta.title {
+                Some(title) => lib::truncate_title(&title, lib::MAX_TITLE_LEN),
+                None => missing_title().to_string(),
+            };
+            let mut embed = serenity::CreateEmbed::default()
+                .author(serenity::CreateEmbedAuthor::new("Now playing"))
+                .title(title);
+            if let Some(url) = meta.url {
+                embed = embed.url(url);
+            }
+            if let Some(thumbnail) = meta.thumbnail_url {
+                embed = embed.thumbnail(thumbnail);
+            }
+            if let Some(duration) = meta.duration {
+                embed = embed.field("Duration", lib::format_duration(&duration), true);
+            }
+            if let Some(channel) = meta.channel {
+                embed = embed.field("Channel", channel, true);
+            }
+
+            let message = serenity::CreateMessage::new().embed(embed);
+            if let Err(e) = channel_id.send_message(&self.http, message).await {
+                tracing::warn!("Failed to announce the current track. {e}");
+            }
+        }
+        None
+    }
+}
+
+/// Keep [METRICS](crate::lib::metrics::METRICS)' voice connection count up to date.
+/// The same instance is registered for connects, reconnects and disconnects, so it knows
+/// whether its call was counted and never counts it twice.
+#[derive(Clone, Default)]
+struct CountConnection {
+    /// Whether this call is currently counted as connected.
+    connected: Arc<AtomicBool>,
+}
+
+impl CountConnection {
+    /// Register this as a global event, for every driver connection event.
+    async fn register(self, call: &CallRef) {
+        tracing::debug!("Registering connection count global event.");
+        let mut call = call.lock().await;
+        for event in [
+            CoreEvent::DriverConnect,
+            CoreEvent::DriverReconnect,
+            CoreEvent::DriverDisconnect,
+        ] {
+            call.add_global_event(Event::Core(event), self.clone());
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for CountConnection {
+    async fn act(&self, ectx: &EventContext<'_>) -> Option<Event> {
+        let connected = !matches!(ectx, EventContext::DriverDisconnect(_));
+        if self.connected.swap(connected, Ordering::SeqCst) != connected {
+            if connected {
+                METRICS.voice_connections.inc();
+            } else {
+                METRICS.voice_connections.dec();
+            }
+        }
         None
     }
 }
 
+/// Hold the queue until `sound` ends, e.g. so the join sound plays before the queue starts.
+/// The current track is paused right away, and queued tracks that start meanwhile are paused too.
+pub async fn hold_queue_for(call: &CallRef, sound: &TrackHandle) {
+    let held = Arc::new(AtomicBool::new(true));
+    {
+        let mut call = call.lock().await;
+        if let Err(e) = call.queue().pause() {
+            tracing::warn!("Failed to pause the queue. {e}");
+        }
+        call.add_global_event(
+            Event::Track(TrackEvent::Play),
+            HoldQueue { held: held.clone() },
+        );
+    }
+
+    let resume = ResumeQueue {
+        call: call.clone(),
+        held,
+    };
+    for event in [TrackEvent::End, TrackEvent::Error] {
+        if sound
+            .add_event(Event::Track(event), resume.clone())
+            .is_err()
+        {
+            // It already ended, so there's nothing to wait for.
+            resume.resume().await;
+            return;
+        }
+    }
+}
+
+/// Pause queued tracks that start while the queue is held, see [hold_queue_for].
+struct HoldQueue {
+    /// Whether the queue is still held, shared with its [ResumeQueue].
+    held: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl EventHandler for HoldQueue {
+    async fn act(&self, ectx: &EventContext<'_>) -> Option<Event> {
+        if !self.held.load(Ordering::SeqCst) {
+            return Some(Event::Cancel);
+        }
+        let EventContext::Track(tracks) = ectx else {
+            return None;
+        };
+
+        for (_, handle) in tracks.iter() {
+            if is_transient(handle).await {
+                continue;
+            }
+            if let Err(e) = handle.pause() {
+                tracing::warn!("Failed to hold a queued track. {e}");
+            }
+        }
+        None
+    }
+}
+
+/// Stop holding the queue and resume it once the sound it was held for is over,
+/// see [hold_queue_for].
+#[derive(Clone)]
+struct ResumeQueue {
+    /// The call whose queue is held.
+    call: CallRef,
+    /// Whether the queue is still held, shared with its [HoldQueue].
+    held: Arc<AtomicBool>,
+}
+
+impl ResumeQueue {
+    /// Resume the queue, unless it already was.
+    async fn resume(&self) {
+        if !self.held.swap(false, Ordering::SeqCst) {
+            return;
+        }
+        if let Err(e) = self.call.lock().await.queue().resume() {
+            tracing::warn!("Failed to resume the queue. {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl EventHandler for ResumeQueue {
+    async fn act(&self, _ectx: &EventContext<'_>) -> Option<Event> {
+        self.resume().await;
+        Some(Event::Cancel)
+    }
+}
+
 /// Remove track metadata from queue when it's done playing.
+/// Also adds the track to the [SessionStats](crate::data::SessionStats) and the guild's
+/// [history](crate::data::GuildData::history).
+///
+/// In [LoopMode::Queue], tracks that ended on their own are added to the back of the queue again.
+/// [LoopMode::Track] is handled by songbird looping the track, so it never ends.
+///
+/// Broken sources can end right after they start, which would drain the queue in an instant.
+/// Such tracks aren't looped, and after [FAST_END_LIMIT] of them in a row the queue is paused
+/// with a notice in the channel the call was started from.
 struct RemoveMeta {
     /// Reference to call.
     call: CallRef,
     /// Reference to queue metadata.
     queue_meta: QueueMeta,
+    /// Reference to the guild's data.
+    guild_data: GuildDataRef,
+    /// Used to re-create looping tracks.
+    http_client: reqwest::Client,
+    /// Used to send the notice when pausing.
+    http: Arc<serenity::Http>,
+    /// Where the notice is sent.
+    channel_id: serenity::ChannelId,
+    /// Tracks ending on their own quicker than this are counted in `fast_ends`.
+    fast_end: Duration,
+    /// How many tracks in a row ended too quickly.
+    fast_ends: AtomicUsize,
 }
 
 impl RemoveMeta {
     /// Constructor for [RemoveMeta]
     async fn new(call: &CallRef, ctx: &Context<'_>) -> Result<Self, ParakeetError> {
         let call = call.clone();
-        let queue_meta = {
-            let guild_data = ctx.guild_data().await?;
-            let lock = guild_data.lock().await;
-            lock.queue_metadata.clone()
-        };
-        Ok(Self { call, queue_meta })
+        let queue_meta = ctx.queue_meta().await?;
+        let guild_data = ctx.guild_data().await?;
+        let http_client = ctx.http_client().await;
+        Ok(Self {
+            call,
+            queue_meta,
+            guild_data,
+            http_client,
+            http: ctx.serenity_context().http.clone(),
+            channel_id: ctx.channel_id(),
+            fast_end: ctx.data().config.fast_end_threshold(),
+            fast_ends: AtomicUsize::new(0),
+        })
     }
 
     /// Register this as a global event
@@ -174,21 +506,86 @@ impl RemoveMeta {
         let mut call = call.lock().await;
         call.add_global_event(Event::Track(TrackEvent::End), self);
     }
+
+    /// Count a track that ended on its own after `play_time`, returning whether it ended too quickly.
+    /// Pauses the queue each time [FAST_END_LIMIT] tracks in a row did.
+    async fn check_fast_end(&self, title: &str, play_time: Duration) -> bool {
+        if play_time >= self.fast_end {
+            self.fast_ends.store(0, Ordering::SeqCst);
+            return false;
+        }
+
+        tracing::warn!("{title} ended after only {play_time:?}, its source may be broken.");
+        let fast_ends = self.fast_ends.fetch_add(1, Ordering::SeqCst) + 1;
+        if fast_ends >= FAST_END_LIMIT {
+            // Counted again from here, so more broken tracks after a `/resume` pause it again.
+            self.fast_ends.store(0, Ordering::SeqCst);
+            tracing::warn!("{fast_ends} tracks in a row ended right away, pausing the queue.");
+            if let Err(e) = self.call.lock().await.queue().pause() {
+                tracing::error!("Failed to pause the queue. {e}");
+            }
+            // The paused timeout starts from here.
+            self.guild_data.lock().await.mark_progress();
+            let notice = format!(
+                "{fast_ends} tracks in a row ended right away, so I paused. \
+                 Use `/skip` to get past broken tracks or `/resume` to keep going."
+            );
+            if let Err(e) = self.channel_id.say(&self.http, notice).await {
+                tracing::warn!("Failed to send the pause notice. {e}");
+            }
+        }
+        true
+    }
 }
 
 #[async_trait]
 impl EventHandler for RemoveMeta {
-    async fn act(&self, _ectx: &EventContext<'_>) -> Option<Event> {
-        let track = self.queue_meta.pop_front().await;
-        match track {
-            None => {
-                tracing::error!("Tried to remove track metadata from empty queue.");
-            }
-            Some(meta) => {
-                let title = meta.title.unwrap_or("<NO TITLE>".to_string());
-                tracing::debug!("Removing metadata for {title}");
-            }
+    async fn act(&self, ectx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::Track(tracks) = ectx else {
+            return None;
         };
+
+        for (state, handle) in tracks.iter() {
+            if is_transient(handle).await {
+                tracing::debug!("Ignoring the end of a transient track.");
+                continue;
+            }
+            let track = remove_finished(&self.call, &self.queue_meta, handle).await;
+            match track {
+                None => {
+                    // Tracks removed from the middle of the queue already had their metadata removed.
+                    tracing::debug!("No metadata left for the finished track.");
+                }
+                Some(meta) => {
+                    let title = meta
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| missing_title().to_string());
+                    tracing::debug!("Removing metadata for {title}");
+
+                    METRICS.tracks_played.inc();
+                    let (loop_mode, volume) = {
+                        let mut guild_data = self.guild_data.lock().await;
+                        guild_data.session.tracks_played += 1;
+                        guild_data.session.time_played += state.play_time;
+                        guild_data.record_played(meta.clone());
+                        (guild_data.loop_mode, guild_data.volume)
+                    };
+
+                    // Skipped or stopped tracks aren't looped, and neither are broken ones.
+                    let ended = state.playing == PlayMode::End;
+                    let fast_end = ended && self.check_fast_end(&title, state.play_time).await;
+                    if loop_mode == LoopMode::Queue && ended && !fast_end {
+                        tracing::debug!("Looping {title} to the back of the queue.");
+                        let http_client = self.http_client.clone();
+                        let tracks = rebuild_tracks(http_client, volume, loop_mode, vec![meta]);
+                        for (track, meta) in tracks {
+                            enqueue_with_meta(&self.call, &self.queue_meta, track, meta).await;
+                        }
+                    }
+                }
+            };
+        }
         None
     }
 }
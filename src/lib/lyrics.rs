@@ -0,0 +1,141 @@
+//! Looking up lyrics with the [lyrics.ovh](https://lyrics.ovh) api,
+//! only used if [Config::lyrics_enabled](crate::Config::lyrics_enabled) is set.
+
+use std::time::Duration;
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+use url::Url;
+
+use crate::ParakeetError;
+
+/// Where lyrics are looked up, followed by `<artist>/<title>`.
+const API_URL: &str = "https://api.lyrics.ovh/v1/";
+
+/// How long to wait for the api before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Suffixes youtube adds to channel names that aren't part of the artist's name.
+const CHANNEL_SUFFIXES: [&str; 3] = [" - Topic", "VEVO", " Official"];
+
+/// The body of a successful lookup.
+#[derive(Deserialize)]
+struct Response {
+    /// The lyrics, with lines separated by `\n`.
+    lyrics: String,
+}
+
+/// Look up the lyrics of `title` by `artist`, `None` if there are none.
+pub async fn lookup(
+    client: &reqwest::Client,
+    artist: &str,
+    title: &str,
+) -> Result<Option<String>, ParakeetError> {
+    let mut url = Url::parse(API_URL).expect("API_URL is a valid url");
+    url.path_segments_mut()
+        .expect("API_URL can be a base")
+        .pop_if_empty()
+        .push(artist)
+        .push(title);
+
+    let response = client.get(url).timeout(TIMEOUT).send().await?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body = response.error_for_status()?.text().await?;
+
+    let Ok(Response { lyrics }) = serde_json::from_str(&body) else {
+        tracing::warn!("Unexpected lyrics response: {body}");
+        return Ok(None);
+    };
+    let lyrics = lyrics.trim();
+    Ok((!lyrics.is_empty()).then(|| lyrics.to_string()))
+}
+
+/// Guess the artist and song title from a video's `title` and `channel`.
+/// Music videos are usually titled "Artist - Song (Official Video)", otherwise the channel
+/// is taken as the artist.
+pub fn guess_artist_title(title: &str, channel: Option<&str>) -> (String, String) {
+    let (artist, song) = match title.split_once(" - ") {
+        Some((artist, song)) => (artist.to_string(), song),
+        None => {
+            let mut artist = channel.unwrap_or_default();
+            for suffix in CHANNEL_SUFFIXES {
+                artist = artist.strip_suffix(suffix).unwrap_or(artist);
+            }
+            (artist.to_string(), title)
+        }
+    };
+
+    // Drop extras like "(Official Video)" or "[Lyrics]".
+    let song = song.find(['(', '[']).map_or(song, |extras| &song[..extras]);
+    (artist.trim().to_string(), song.trim().to_string())
+}
+
+/// Split `text` into chunks of at most `max` bytes, between lines where possible.
+pub fn chunk(text: &str, max: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut buffer = String::new();
+
+    for line in text.lines() {
+        if !buffer.is_empty() && buffer.len() + line.len() + 1 > max {
+            chunks.push(std::mem::take(&mut buffer));
+        }
+        // A single line that doesn't fit is split between chars.
+        for c in line.chars() {
+            if buffer.len() + c.len_utf8() > max {
+                chunks.push(std::mem::take(&mut buffer));
+            }
+            buffer.push(c);
+        }
+        if buffer.len() < max {
+            buffer.push('\n');
+        }
+    }
+    if !buffer.trim().is_empty() {
+        chunks.push(buffer);
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_from_music_videos() {
+        let guess = |title, channel| guess_artist_title(title, channel);
+
+        assert_eq!(
+            guess(
+                "Rick Astley - Never Gonna Give You Up (Official Video)",
+                None
+            ),
+            (
+                "Rick Astley".to_string(),
+                "Never Gonna Give You Up".to_string()
+            )
+        );
+        assert_eq!(
+            guess("Bohemian Rhapsody [Remastered]", Some("Queen - Topic")),
+            ("Queen".to_string(), "Bohemian Rhapsody".to_string())
+        );
+        assert_eq!(
+            guess("Hello", Some("AdeleVEVO")),
+            ("Adele".to_string(), "Hello".to_string())
+        );
+    }
+
+    #[test]
+    fn chunks_fit_and_keep_lines() {
+        let text = "first line\nsecond line\nthird";
+        let chunks = chunk(text, 24);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 24));
+        assert_eq!(chunks, vec!["first line\nsecond line\n", "third\n"]);
+
+        // Lines longer than a chunk are split.
+        let chunks = chunk(&"a".repeat(10), 4);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 4));
+        assert_eq!(chunks.concat().trim(), "a".repeat(10));
+    }
+}
@@ -0,0 +1,119 @@
+//! Reading and writing [m3u](https://en.wikipedia.org/wiki/M3U) playlists.
+
+use url::Url;
+
+use crate::data::TrackMetadata;
+
+/// A playable entry read from an m3u playlist.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Where the track can be played from.
+    pub url: Url,
+    /// The title from the `#EXTINF` line, if there was one.
+    pub title: Option<String>,
+}
+
+/// Write `tracks` as an extended m3u playlist.
+/// Tracks without a url can't be played anywhere else, so they're left out.
+pub fn to_m3u<'a>(tracks: impl IntoIterator<Item = &'a TrackMetadata>) -> String {
+    let mut content = String::from("#EXTM3U\n");
+    for track in tracks {
+        let Some(url) = &track.url else {
+            continue;
+        };
+        // Unknown durations are written as -1.
+        let secs = track
+            .duration
+            .map_or("-1".to_string(), |dur| dur.as_secs().to_string());
+        let title = track
+            .title
+            .as_deref()
+            .unwrap_or_default()
+            .replace('\n', " ");
+        content.push_str(&format!("#EXTINF:{secs},{title}\n{url}\n"));
+    }
+    content
+}
+
+/// Read the entries of an m3u playlist.
+/// Entries that aren't urls (e.g. local files) are skipped.
+pub fn parse_m3u(content: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut title = None;
+
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            title = info
+                .split_once(',')
+                .map(|(_, title)| title.trim().to_string())
+                .filter(|title| !title.is_empty());
+        } else if line.starts_with('#') {
+            continue;
+        } else {
+            // The title only belongs to the line right after it.
+            let title = title.take();
+            if let Ok(url) = Url::parse(line) {
+                entries.push(Entry { url, title });
+            }
+        }
+    }
+
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn m3u_round_trips() {
+        let track = |title: Option<&str>, duration, url: Option<&str>| TrackMetadata {
+            id: uuid::Uuid::nil(),
+            title: title.map(str::to_string),
+            duration,
+            channel: None,
+            thumbnail_url: None,
+            url: url.map(str::to_string),
+            requested_by: None,
+        };
+        let tracks = [
+            track(
+                Some("First"),
+                Some(Duration::from_secs(61)),
+                Some("https://youtu.be/a"),
+            ),
+            track(None, None, Some("https://youtu.be/b")),
+            track(Some("No url"), None, None),
+        ];
+
+        let content = to_m3u(&tracks);
+        assert_eq!(
+            content,
+            "#EXTM3U\n#EXTINF:61,First\nhttps://youtu.be/a\n#EXTINF:-1,\nhttps://youtu.be/b\n"
+        );
+
+        let entries = parse_m3u(&content);
+        assert_eq!(
+            entries,
+            vec![
+                Entry {
+                    url: Url::parse("https://youtu.be/a").unwrap(),
+                    title: Some("First".to_string()),
+                },
+                Entry {
+                    url: Url::parse("https://youtu.be/b").unwrap(),
+                    title: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn m3u_skips_local_files() {
+        let entries = parse_m3u("#EXTINF:10,Local\nmusic/song.mp3\nhttps://youtu.be/a\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].title, None);
+    }
+}
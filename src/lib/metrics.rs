@@ -0,0 +1,171 @@
+//! Prometheus metrics, served over http if [Config::metrics_port](crate::Config::metrics_port) is set.
+//!
+//! Metrics are recorded whether or not they're served, since it's only a few atomic increments.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::exponential_buckets;
+use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::registry::Registry;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+
+/// The bot's metrics, see [Metrics].
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Labels for [Metrics::commands].
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CommandLabels {
+    /// The name of the command.
+    pub command: String,
+}
+
+/// Everything that's exported, along with the registry that encodes it.
+pub struct Metrics {
+    /// Encodes the metrics below.
+    registry: Registry,
+    /// Commands invoked, by name.
+    pub commands: Family<CommandLabels, Counter>,
+    /// Tracks that finished playing.
+    pub tracks_played: Counter,
+    /// Voice channels the bot is currently in.
+    pub voice_connections: Gauge,
+    /// Times a search backend (e.g. yt-dlp) was spawned.
+    pub ytdlp_spawns: Counter,
+    /// How long search backend calls took, in seconds.
+    pub ytdlp_latency: Histogram,
+}
+
+impl Metrics {
+    /// Create and register every metric.
+    fn new() -> Self {
+        let mut registry = Registry::with_prefix("parakeet");
+        let commands = Family::<CommandLabels, Counter>::default();
+        let tracks_played = Counter::default();
+        let voice_connections = Gauge::default();
+        let ytdlp_spawns = Counter::default();
+        // 0.1s up to ~100s.
+        let ytdlp_latency = Histogram::new(exponential_buckets(0.1, 2.0, 11));
+
+        registry.register("commands", "Commands invoked", commands.clone());
+        registry.register(
+            "tracks_played",
+            "Tracks that finished playing",
+            tracks_played.clone(),
+        );
+        registry.register(
+            "voice_connections",
+            "Voice channels the bot is in",
+            voice_connections.clone(),
+        );
+        registry.register(
+            "ytdlp_spawns",
+            "Times the search backend was spawned",
+            ytdlp_spawns.clone(),
+        );
+        registry.register(
+            "ytdlp_latency_seconds",
+            "How long search backend calls took",
+            ytdlp_latency.clone(),
+        );
+
+        Self {
+            registry,
+            commands,
+            tracks_played,
+            voice_connections,
+            ytdlp_spawns,
+            ytdlp_latency,
+        }
+    }
+
+    /// Count an invocation of `command`.
+    pub fn command_invoked(&self, command: &str) {
+        let labels = CommandLabels {
+            command: command.to_string(),
+        };
+        self.commands.get_or_create(&labels).inc();
+    }
+
+    /// Count a search backend call that took `elapsed`.
+    pub fn ytdlp_spawned(&self, elapsed: Duration) {
+        self.ytdlp_spawns.inc();
+        self.ytdlp_latency.observe(elapsed.as_secs_f64());
+    }
+
+    /// The metrics in the prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        encode(&mut out, &self.registry).expect("writing to a string can't fail");
+        out
+    }
+}
+
+/// Serve [METRICS] to every http request on `port`, regardless of path.
+/// Failing to bind is only logged, the bot works fine without metrics.
+pub async fn serve(port: u16) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!("Failed to serve metrics on port {port}. {e}");
+            return;
+        }
+    };
+    tracing::info!("Serving metrics on port {port}.");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(respond(stream));
+            }
+            Err(e) => tracing::warn!("Failed to accept metrics connection. {e}"),
+        }
+    }
+}
+
+/// Answer a single request with the encoded metrics.
+async fn respond(mut stream: TcpStream) {
+    // The request itself doesn't matter, but it has to be read before answering.
+    let mut buf = [0; 1024];
+    if stream.read(&mut buf).await.is_err() {
+        return;
+    }
+
+    let body = METRICS.encode();
+    let response = format!(
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(e) = stream.write_all(response.as_bytes()).await {
+        tracing::debug!("Failed to send metrics. {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_are_encoded() {
+        let metrics = Metrics::new();
+        metrics.command_invoked("play");
+        metrics.command_invoked("play");
+        metrics.ytdlp_spawned(Duration::from_millis(300));
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(r#"parakeet_commands_total{command="play"} 2"#));
+        assert!(encoded.contains("parakeet_ytdlp_spawns_total 1"));
+        assert!(encoded.contains("parakeet_ytdlp_latency_seconds_count 1"));
+    }
+}
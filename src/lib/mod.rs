@@ -1,11 +1,65 @@
 //! Misc
 
 pub mod call;
+pub mod eta;
 pub mod events;
+pub mod lyrics;
+pub mod m3u;
+pub mod metrics;
+pub mod spotify;
 pub mod youtube;
 
+use std::collections::VecDeque;
 use std::time::Duration;
 
+use poise::CreateReply;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::error::UserError;
+use crate::serenity;
+use crate::Context;
+use crate::ParakeetError;
+
+/// Discord allows embed titles of at most this many chars.
+pub const MAX_TITLE_LEN: usize = 256;
+
+/// Discord accepts followups to an interaction for 15 minutes, this leaves some margin.
+const FOLLOWUP_WINDOW: Duration = Duration::from_secs(14 * 60);
+
+/// Defer the response to the interaction, see [poise::Context::defer].
+/// This is best-effort, so a failure (e.g. the interaction expired) is only logged.
+pub async fn defer(ctx: &Context<'_>) {
+    if let Err(e) = ctx.defer().await {
+        tracing::warn!("Failed to defer response: {e}");
+    }
+}
+
+/// Send `reply` at the end of a command that may have taken a while, like [poise::Context::send].
+/// If the interaction is too old to respond to, the reply is posted in the channel instead.
+pub async fn send_late(ctx: &Context<'_>, reply: CreateReply) -> Result<(), ParakeetError> {
+    let age = serenity::Timestamp::now().unix_timestamp() - ctx.created_at().unix_timestamp();
+    if age < FOLLOWUP_WINDOW.as_secs() as i64 {
+        ctx.send(reply).await?;
+        return Ok(());
+    }
+
+    tracing::debug!("Interaction is {age}s old, replying in the channel instead.");
+    let mut message = serenity::CreateMessage::new()
+        .embeds(reply.embeds)
+        .add_files(reply.attachments);
+    if let Some(content) = reply.content {
+        message = message.content(format!("{}: {content}", ctx.author()));
+    }
+    if let Some(components) = reply.components {
+        message = message.components(components);
+    }
+    ctx.channel_id().send_message(ctx, message).await?;
+    Ok(())
+}
+
 /// Helper function to format a duration.
 pub fn format_duration(dur: &Duration) -> String {
     let total_secs = dur.as_secs();
@@ -21,3 +75,140 @@ pub fn format_duration(dur: &Duration) -> String {
         format!("[{mins:02}m:{secs:02}s]")
     }
 }
+
+/// Shorten `title` to at most `max` chars, ending with an ellipsis if anything was cut.
+/// Only cuts between graphemes, so accents and emoji are never split.
+pub fn truncate_title(title: &str, max: usize) -> String {
+    if title.chars().count() <= max {
+        return title.to_string();
+    }
+
+    let mut truncated = String::new();
+    let mut len = 0;
+    for grapheme in title.graphemes(true) {
+        len += grapheme.chars().count();
+        // Leave room for the ellipsis.
+        if len >= max {
+            break;
+        }
+        truncated.push_str(grapheme);
+    }
+    truncated.push('…');
+    truncated
+}
+
+/// Parse a timestamp in `hh:mm:ss`, `mm:ss`, or plain seconds, the counterpart to [format_duration].
+pub fn parse_duration(input: &str) -> Result<Duration, UserError> {
+    let bad_args = || UserError::BadArgs {
+        input: Some(input.to_string()),
+    };
+
+    let parts: Vec<u64> = input
+        .trim()
+        .split(':')
+        .map(|part| part.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| bad_args())?;
+    // Huge values are rejected instead of overflowing.
+    let secs = match parts[..] {
+        [secs] => Some(secs),
+        [mins, secs] if secs < 60 => mins.checked_mul(60).and_then(|m| m.checked_add(secs)),
+        [hours, mins, secs] if mins < 60 && secs < 60 => hours
+            .checked_mul(3600)
+            .and_then(|h| h.checked_add(mins * 60 + secs)),
+        _ => None,
+    };
+    Ok(Duration::from_secs(secs.ok_or_else(bad_args)?))
+}
+
+/// Move the element at index `from` to index `to`, shifting the elements in between.
+/// Returns `false` (and does nothing) if either index is out of range.
+pub fn move_in_deque<T>(deque: &mut VecDeque<T>, from: usize, to: usize) -> bool {
+    if from >= deque.len() || to >= deque.len() {
+        return false;
+    }
+    if let Some(item) = deque.remove(from) {
+        deque.insert(to, item);
+    }
+    true
+}
+
+/// A random order for `len` queued tracks from `seed`, where the tracks before `start` stay put.
+/// The element at index `i` comes from index `order[i]`, see [permute_deque].
+pub fn shuffled_order(len: usize, start: usize, seed: u64) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..len).collect();
+    if start < len {
+        order[start..].shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    order
+}
+
+/// Reorder `deque` so the element at index `i` is the one that was at index `order[i]`.
+/// Returns `false` (and does nothing) if `order` isn't a reordering of the deque's indices.
+pub fn permute_deque<T>(deque: &mut VecDeque<T>, order: &[usize]) -> bool {
+    if order.len() != deque.len() {
+        return false;
+    }
+    let mut seen = vec![false; order.len()];
+    for &index in order {
+        if index >= seen.len() || std::mem::replace(&mut seen[index], true) {
+            return false;
+        }
+    }
+
+    let mut old: Vec<Option<T>> = deque.drain(..).map(Some).collect();
+    deque.extend(order.iter().filter_map(|&index| old[index].take()));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse() {
+        let secs = |secs| Some(Duration::from_secs(secs));
+
+        assert_eq!(parse_duration("90").ok(), secs(90));
+        assert_eq!(parse_duration("1:30").ok(), secs(90));
+        assert_eq!(parse_duration(" 01:02:03 ").ok(), secs(3723));
+        assert_eq!(parse_duration("61:00").ok(), secs(3660));
+        assert!(parse_duration("1:60").is_err());
+        assert!(parse_duration("1:2:3:4").is_err());
+        assert!(parse_duration("-5").is_err());
+        assert!(parse_duration("an hour").is_err());
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration(&format!("{}:00:00", u64::MAX / 1000)).is_err());
+        assert!(parse_duration(&format!("{}:00", u64::MAX)).is_err());
+    }
+
+    #[test]
+    fn titles_truncate_between_graphemes() {
+        assert_eq!(truncate_title("short", 10), "short");
+        assert_eq!(truncate_title("exactly 10", 10), "exactly 10");
+        assert_eq!(truncate_title("a bit too long", 10), "a bit too…");
+
+        // Multi-byte chars are kept whole.
+        let title = "日本語のタイトル".repeat(40);
+        let truncated = truncate_title(&title, MAX_TITLE_LEN);
+        assert_eq!(truncated.chars().count(), MAX_TITLE_LEN);
+        assert!(truncated.ends_with("…"));
+
+        // So are graphemes made of several chars, even if that leaves it a bit shorter.
+        let title = "e\u{301}".repeat(10);
+        let truncated = truncate_title(&title, 6);
+        assert_eq!(truncated, format!("{}…", "e\u{301}".repeat(2)));
+    }
+
+    #[test]
+    fn shuffles_only_from_start() {
+        for seed in 0..20 {
+            let order = shuffled_order(10, 4, seed);
+            assert_eq!(order[..4], [0, 1, 2, 3]);
+            let mut rest = order[4..].to_vec();
+            rest.sort();
+            assert_eq!(rest, (4..10).collect::<Vec<_>>());
+        }
+        assert_eq!(shuffled_order(3, 5, 0), [0, 1, 2]);
+    }
+}
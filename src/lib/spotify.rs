@@ -0,0 +1,170 @@
+//! Reading spotify links, so their tracks can be searched for on youtube instead.
+//!
+//! Spotify's audio can't be played, so only the metadata on its pages is used: the
+//! `og:title` and `og:description` of a track, and the `music:song` links of an album or playlist.
+
+use std::time::Duration;
+
+use url::Url;
+
+use crate::error::UserError;
+use crate::ParakeetError;
+
+/// The most tracks taken from a spotify album or playlist, each one is a separate search.
+pub const COLLECTION_LIMIT: usize = 25;
+
+/// How long to wait for a spotify page before giving up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What a spotify link points to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Link {
+    /// A single track.
+    Track,
+    /// An album or a playlist.
+    Collection,
+}
+
+impl Link {
+    /// What `url` points to, `None` for things that aren't music (e.g. podcasts).
+    pub fn of(url: &Url) -> Option<Link> {
+        let mut segments = url.path_segments()?;
+        let mut kind = segments.next()?;
+        // Localized links start with e.g. `intl-de`.
+        if kind.starts_with("intl-") {
+            kind = segments.next()?;
+        }
+        match kind {
+            "track" => Some(Link::Track),
+            "album" | "playlist" => Some(Link::Collection),
+            _ => None,
+        }
+    }
+}
+
+/// What to search for to find the spotify track at `url`, as "artist title".
+pub async fn track_query(client: &reqwest::Client, url: &Url) -> Result<String, ParakeetError> {
+    let page = fetch(client, url).await?;
+    Ok(query_from_page(&page).ok_or_else(|| unreadable("track"))?)
+}
+
+/// The tracks of the spotify album or playlist at `url`, at most [COLLECTION_LIMIT].
+pub async fn collection_tracks(
+    client: &reqwest::Client,
+    url: &Url,
+) -> Result<Vec<Url>, ParakeetError> {
+    let page = fetch(client, url).await?;
+    let tracks: Vec<Url> = meta_contents(&page, "music:song")
+        .into_iter()
+        .filter_map(|track| Url::parse(&track).ok())
+        .take(COLLECTION_LIMIT)
+        .collect();
+    if tracks.is_empty() {
+        Err(unreadable("album or playlist"))?
+    }
+    Ok(tracks)
+}
+
+/// The error for a spotify page without the expected metadata.
+fn unreadable(what: &str) -> UserError {
+    UserError::SearchFailed {
+        reason: format!("Couldn't read the Spotify {what}."),
+    }
+}
+
+/// Download the page at `url`.
+async fn fetch(client: &reqwest::Client, url: &Url) -> Result<String, ParakeetError> {
+    let response = client.get(url.clone()).timeout(TIMEOUT).send().await?;
+    Ok(response.error_for_status()?.text().await?)
+}
+
+/// Build a search query from a track page.
+/// The description looks like "Artist · Album · Song · 1987", so the artist comes first.
+fn query_from_page(page: &str) -> Option<String> {
+    let title = meta_contents(page, "og:title").into_iter().next()?;
+    let artist = meta_contents(page, "og:description")
+        .into_iter()
+        .next()
+        .and_then(|description| {
+            let artist = description.split(" · ").next()?.trim().to_string();
+            (!artist.is_empty()).then_some(artist)
+        });
+    Some(match artist {
+        Some(artist) => format!("{artist} {title}"),
+        None => title,
+    })
+}
+
+/// The `content` of every `<meta>` tag whose `property` or `name` is `key`, in page order.
+fn meta_contents(page: &str, key: &str) -> Vec<String> {
+    let property = format!("property=\"{key}\"");
+    let name = format!("name=\"{key}\"");
+
+    page.split("<meta")
+        .skip(1)
+        .filter_map(|tag| {
+            let tag = &tag[..tag.find('>')?];
+            if !tag.contains(&property) && !tag.contains(&name) {
+                return None;
+            }
+            let content = tag.split("content=\"").nth(1)?;
+            let content = &content[..content.find('"')?];
+            Some(decode_entities(content))
+        })
+        .collect()
+}
+
+/// Decode the html entities spotify uses in attributes.
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_track_and_collection_pages() {
+        let track = r#"<head><meta property="og:title" content="Don&#x27;t Stop Me Now"/>
+            <meta property="og:description" content="Queen · Jazz · Song · 1978"/></head>"#;
+        assert_eq!(
+            query_from_page(track).as_deref(),
+            Some("Queen Don't Stop Me Now")
+        );
+        assert_eq!(query_from_page("<head></head>"), None);
+
+        let album = r#"<meta name="music:song" content="https://open.spotify.com/track/a"/>
+            <meta name="music:musician" content="https://open.spotify.com/artist/b"/>
+            <meta name="music:song" content="https://open.spotify.com/track/c"/>"#;
+        assert_eq!(
+            meta_contents(album, "music:song"),
+            vec![
+                "https://open.spotify.com/track/a",
+                "https://open.spotify.com/track/c"
+            ]
+        );
+    }
+
+    #[test]
+    fn classifies_links() {
+        let link = |url: &str| Link::of(&Url::parse(url).unwrap());
+        assert_eq!(
+            link("https://open.spotify.com/track/abc?si=x"),
+            Some(Link::Track)
+        );
+        assert_eq!(
+            link("https://open.spotify.com/intl-de/album/abc"),
+            Some(Link::Collection)
+        );
+        assert_eq!(
+            link("https://open.spotify.com/playlist/abc"),
+            Some(Link::Collection)
+        );
+        assert_eq!(link("https://open.spotify.com/episode/abc"), None);
+    }
+}
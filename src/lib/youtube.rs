@@ -1,48 +1,280 @@
 //! * Functionality for interfacing with youtube (e.g. searches).
+//!
+//! Searches go through `yt-dlp`, or the older `youtube-dl` on systems that only have that,
+//! see [Backend].
 
+use std::collections::VecDeque;
+use std::future::Future;
+use std::process::Output;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::Mutex;
 use tracing::instrument;
 
+use crate::lib;
+use crate::lib::metrics::METRICS;
+use crate::lib::spotify;
 use crate::{error::UserError, ParakeetError};
 
+/// The max amount of entries taken from a playlist at once.
+pub const PLAYLIST_LIMIT: usize = 50;
+
+/// How many distinct queries [SEARCH_CACHE] remembers.
+const SEARCH_CACHE_LEN: usize = 100;
+
+/// Recent [search_query] results, so autocomplete doesn't search again on every keystroke.
+static SEARCH_CACHE: Mutex<SearchCache> = Mutex::const_new(SearchCache::new(SEARCH_CACHE_LEN));
+
 /// A youtube video with formatted metadata and its url.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct SearchResult {
     /// Display name
     pub name: String,
     /// The url of source
     pub url: String,
+    /// Title of the video, if the backend reported it.
+    pub title: Option<String>,
+    /// Duration of the video, if the backend reported it.
+    pub duration: Option<Duration>,
+    /// Name of the uploader, if the backend reported it.
+    pub channel: Option<String>,
+    /// Url of the video's thumbnail, if the backend reported it.
+    pub thumbnail: Option<String>,
+}
+
+/// The program used to search youtube.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// [yt-dlp](https://github.com/yt-dlp/yt-dlp)
+    #[default]
+    YtDlp,
+    /// [youtube-dl](https://github.com/ytdl-org/youtube-dl), which yt-dlp is a fork of.
+    YoutubeDl,
+}
+
+impl Backend {
+    /// The name of the executable.
+    fn program(self) -> &'static str {
+        match self {
+            Backend::YtDlp => "yt-dlp",
+            Backend::YoutubeDl => "youtube-dl",
+        }
+    }
+
+    /// What to use if this backend isn't installed.
+    fn fallback(self) -> Backend {
+        match self {
+            Backend::YtDlp => Backend::YoutubeDl,
+            Backend::YoutubeDl => Backend::YtDlp,
+        }
+    }
+}
+
+/// How `/play` handles a search query.
+#[derive(
+    Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchStrategy {
+    /// Play the top result.
+    #[default]
+    Auto,
+    /// Let the user pick from the top results.
+    Prompt,
+}
+
+/// Why yt-dlp couldn't get a video, see [classify].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unavailable {
+    /// Blocked in the bot's region.
+    GeoRestricted,
+    /// Needs a signed-in account to confirm age.
+    AgeRestricted,
+    /// Private, deleted, or otherwise gone.
+    Private,
+    /// Couldn't reach the site.
+    Network,
+    /// The url isn't something yt-dlp can play.
+    UnsupportedUrl,
+}
+
+impl std::fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            Unavailable::GeoRestricted => "That video isn't available in my region.",
+            Unavailable::AgeRestricted => "That video is age-restricted, so I can't play it.",
+            Unavailable::Private => "That video is private or has been removed.",
+            Unavailable::Network => "Couldn't reach the site, try again in a bit.",
+            Unavailable::UnsupportedUrl => "I can't play anything from that url.",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+/// Snippets of yt-dlp's error messages (lowercased) and what they mean.
+const UNAVAILABLE_PATTERNS: &[(&str, Unavailable)] = &[
+    ("available in your country", Unavailable::GeoRestricted),
+    ("blocked it in your country", Unavailable::GeoRestricted),
+    ("geo restrict", Unavailable::GeoRestricted),
+    ("confirm your age", Unavailable::AgeRestricted),
+    ("age-restricted", Unavailable::AgeRestricted),
+    ("inappropriate for some users", Unavailable::AgeRestricted),
+    ("private video", Unavailable::Private),
+    ("video unavailable", Unavailable::Private),
+    ("has been removed", Unavailable::Private),
+    (
+        "account associated with this video has been terminated",
+        Unavailable::Private,
+    ),
+    ("unsupported url", Unavailable::UnsupportedUrl),
+    ("is not a valid url", Unavailable::UnsupportedUrl),
+    ("unable to download webpage", Unavailable::Network),
+    ("urlopen error", Unavailable::Network),
+    ("timed out", Unavailable::Network),
+    ("temporary failure in name resolution", Unavailable::Network),
+    ("connection reset", Unavailable::Network),
+];
+
+/// Figure out why yt-dlp failed from its error output, if it's a known reason.
+pub fn classify(stderr: &str) -> Option<Unavailable> {
+    let stderr = stderr.to_lowercase();
+    UNAVAILABLE_PATTERNS
+        .iter()
+        .find(|(pattern, _)| stderr.contains(pattern))
+        .map(|(_, reason)| *reason)
+}
+
+/// The most chars of yt-dlp's error output shown to users.
+const MAX_ERROR_LEN: usize = 200;
+
+/// The first line of yt-dlp's error output, without the `ERROR: [extractor] id:` prefix.
+/// Returns `None` if there is no output.
+fn clean_error(stderr: &str) -> Option<String> {
+    let line = stderr
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+    let line = line.strip_prefix("ERROR:").unwrap_or(line).trim_start();
+    // Extractor errors look like `[youtube] abc123: message`.
+    let line = match line.strip_prefix('[').and_then(|rest| rest.split_once("]")) {
+        Some((_, rest)) => rest.split_once(": ").map_or(rest, |(_, msg)| msg).trim(),
+        None => line,
+    };
+    Some(line.chars().take(MAX_ERROR_LEN).collect())
 }
 
 /// Searches youtube for the given query.
 ///
 /// `limit` is the max amount of results to get.
+/// Results are reused for `ttl` if the same query is searched again, see [SearchCache].
 #[instrument(fields(query=query.as_ref()))]
 pub async fn search_query(
     query: impl AsRef<str>,
     limit: u8,
+    backend: Backend,
+    ttl: Duration,
 ) -> Result<Vec<SearchResult>, ParakeetError> {
-    let uri = &format!("ytsearch{limit}:{}", query.as_ref());
-    search(uri).await
+    let query = query.as_ref();
+    let key = CacheKey::new(query, limit, backend);
+    let uri = format!("ytsearch{limit}:{query}");
+    SearchCache::get_or_search(&SEARCH_CACHE, key, ttl, || search(uri, backend)).await
 }
 
-/// Searches youtube for the given query.
-/// Returns the first result.
-/// `limit` is the max amount of results to get.
-#[instrument(err, fields(query=query.as_ref()))]
-pub async fn search_best(query: impl AsRef<str>) -> Result<SearchResult, ParakeetError> {
-    let uri = &format!("ytsearch1:{}", query.as_ref());
-    let results = search(uri).await?;
-    match results.into_iter().next() {
-        Some(search_result) => Ok(search_result),
-        None => Err(UserError::SearchFailed {
-            reason: "No results found.".to_string(),
-        })?,
+/// What a cached search is looked up by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CacheKey {
+    /// The query, lowercased and with whitespace collapsed.
+    query: String,
+    /// The max amount of results searched for.
+    limit: u8,
+    /// The program that searched.
+    backend: Backend,
+}
+
+impl CacheKey {
+    /// Normalize `query` so trivially different queries share an entry.
+    fn new(query: &str, limit: u8, backend: Backend) -> Self {
+        let query = query
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        Self {
+            query,
+            limit,
+            backend,
+        }
+    }
+}
+
+/// A least recently used cache of search results, most recent first.
+/// Entries older than the ttl given on lookup are dropped instead of returned.
+struct SearchCache {
+    /// Cached results and when they were searched.
+    entries: VecDeque<(CacheKey, Instant, Vec<SearchResult>)>,
+    /// The most entries kept, the least recently used one is evicted past this.
+    capacity: usize,
+}
+
+impl SearchCache {
+    /// An empty cache holding at most `capacity` entries.
+    const fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Cached results for `key`, if they're younger than `ttl`.
+    fn get(&mut self, key: &CacheKey, ttl: Duration) -> Option<Vec<SearchResult>> {
+        self.entries
+            .retain(|(_, searched_at, _)| searched_at.elapsed() < ttl);
+        let pos = self.entries.iter().position(|(k, _, _)| k == key)?;
+        let entry = self.entries.remove(pos)?;
+        let results = entry.2.clone();
+        self.entries.push_front(entry);
+        Some(results)
+    }
+
+    /// Remember `results` for `key`, evicting the least recently used entry if full.
+    fn insert(&mut self, key: CacheKey, results: Vec<SearchResult>) {
+        self.entries.retain(|(k, _, _)| *k != key);
+        self.entries.push_front((key, Instant::now(), results));
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Look up `key` in `cache`, calling `search` and caching its results on a miss.
+    /// Failed searches aren't cached. The lock isn't held while searching.
+    async fn get_or_search<F, Fut>(
+        cache: &Mutex<SearchCache>,
+        key: CacheKey,
+        ttl: Duration,
+        search: F,
+    ) -> Result<Vec<SearchResult>, ParakeetError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<SearchResult>, ParakeetError>>,
+    {
+        if let Some(results) = cache.lock().await.get(&key, ttl) {
+            tracing::debug!("Search cache hit");
+            return Ok(results);
+        }
+        let results = search().await?;
+        if !ttl.is_zero() {
+            cache.lock().await.insert(key, results.clone());
+        }
+        Ok(results)
     }
 }
 
 /// Searches youtube for the given link.
 #[instrument(err)]
-pub async fn search_link(url: url::Url) -> Result<SearchResult, ParakeetError> {
-    let results = search(url).await?;
+pub async fn search_link(url: url::Url, backend: Backend) -> Result<SearchResult, ParakeetError> {
+    let results = search(url, backend).await?;
     match results.into_iter().next() {
         None => Err(UserError::SearchFailed {
             reason: "No results found".to_string(),
@@ -51,8 +283,101 @@ pub async fn search_link(url: url::Url) -> Result<SearchResult, ParakeetError> {
     }
 }
 
-/// Helper function that actually calls yt-dlp.
-async fn search(uri: impl AsRef<str>) -> Result<Vec<SearchResult>, ParakeetError> {
+/// Finds the spotify track at `url` on youtube, by searching for its artist and title.
+/// Results are cached like [search_query].
+#[instrument(skip(client), err)]
+pub async fn resolve_spotify(
+    client: &reqwest::Client,
+    url: &url::Url,
+    backend: Backend,
+    ttl: Duration,
+) -> Result<SearchResult, ParakeetError> {
+    let query = spotify::track_query(client, url).await?;
+    tracing::debug!("Searching youtube for '{query}'");
+    match search_query(&query, 1, backend, ttl)
+        .await?
+        .into_iter()
+        .next()
+    {
+        None => Err(UserError::SearchFailed {
+            reason: format!("Couldn't find '{query}' on YouTube."),
+        })?,
+        Some(result) => Ok(result),
+    }
+}
+
+/// Lists the entries of the youtube playlist `list`, starting from the 1-based position `start`.
+/// At most [PLAYLIST_LIMIT] entries are returned.
+#[instrument(err)]
+pub async fn playlist_from(
+    list: &str,
+    start: usize,
+    backend: Backend,
+) -> Result<Vec<SearchResult>, ParakeetError> {
+    let uri = format!("https://www.youtube.com/playlist?list={list}");
+    // Both backends understand the `start-end` range format.
+    let items = format!("{start}-{}", start + PLAYLIST_LIMIT - 1);
+    let results = search_with(uri, &["--playlist-items", &items], backend).await?;
+    if results.is_empty() {
+        Err(UserError::SearchFailed {
+            reason: "Empty playlist.".to_string(),
+        })?
+    }
+    Ok(results)
+}
+
+/// Helper function that actually calls the search [Backend].
+async fn search(
+    uri: impl AsRef<str>,
+    backend: Backend,
+) -> Result<Vec<SearchResult>, ParakeetError> {
+    search_with(uri, &[], backend).await
+}
+
+/// Calls `backend` with `extra_args` added before the `uri`.
+/// If `backend` isn't installed, its [fallback](Backend::fallback) is tried instead.
+async fn search_with(
+    uri: impl AsRef<str>,
+    extra_args: &[&str],
+    backend: Backend,
+) -> Result<Vec<SearchResult>, ParakeetError> {
+    let uri = uri.as_ref();
+    let (backend, output) = match run(backend, uri, extra_args).await {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let fallback = backend.fallback();
+            tracing::warn!(
+                "{} not found, falling back to {}",
+                backend.program(),
+                fallback.program()
+            );
+            (fallback, run(fallback, uri, extra_args).await)
+        }
+        output => (backend, output),
+    };
+    let output = output.map_err(ParakeetError::IoError)?;
+    tracing::debug!("Searched with {}", backend.program());
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if let Some(reason) = classify(&stderr) {
+            Err(UserError::VideoUnavailable { reason })?
+        }
+        let reason = clean_error(&stderr)
+            .unwrap_or_else(|| format!("{} exited with {}", backend.program(), output.status));
+        Err(UserError::SearchFailed { reason })?
+    }
+
+    // Convert `Output` into a string, this should never fail
+    let out_string = String::from_utf8(output.stdout).map_err(ParakeetError::Utf8Error)?;
+
+    Ok(match backend {
+        Backend::YtDlp => parse_printed(&out_string),
+        Backend::YoutubeDl => parse_json(&out_string),
+    })
+}
+
+/// Spawns `backend` and waits for its output.
+async fn run(backend: Backend, uri: &str, extra_args: &[&str]) -> std::io::Result<Output> {
     // Discord enforces a 100 char limit so we budget
     // Format is title[duration](views)-channel
     let format: &str = &[
@@ -66,36 +391,262 @@ async fn search(uri: impl AsRef<str>) -> Result<Vec<SearchResult>, ParakeetError
     ]
     .concat();
 
-    let ytdlp_args = [
-        "--no-warnings",
-        "--ignore-config",
-        "--flat-playlist",
-        "--print",
-        format,
-        "--print",
-        "webpage_url",
-        uri.as_ref(),
-    ];
-
-    let ytdlp_output = tokio::process::Command::new("yt-dlp")
-        .args(ytdlp_args)
+    // youtube-dl can't `--print`, so it dumps json to be formatted here instead.
+    let args = match backend {
+        Backend::YtDlp => vec![
+            "--no-warnings",
+            "--ignore-config",
+            "--flat-playlist",
+            "--print",
+            format,
+            "--print",
+            "webpage_url",
+            // Raw metadata, so the video doesn't have to be looked up again to be played.
+            "--print",
+            "%(.{title,duration,channel,thumbnail,thumbnails})j",
+        ],
+        Backend::YoutubeDl => vec![
+            "--no-warnings",
+            "--ignore-config",
+            "--flat-playlist",
+            "--dump-json",
+        ],
+    };
+
+    let start = Instant::now();
+    let output = tokio::process::Command::new(backend.program())
+        .args(args)
+        .args(extra_args)
+        .arg(uri)
         .stdin(std::process::Stdio::null())
         .output()
-        .await
-        .map_err(ParakeetError::IoError)?;
-
-    // Convert `Output` into a string, this should never fail
-    let out_string = String::from_utf8(ytdlp_output.stdout).map_err(ParakeetError::Utf8Error)?;
+        .await;
+    METRICS.ytdlp_spawned(start.elapsed());
+    output
+}
 
-    let mut iter = out_string.split('\n');
+/// Parse the output of `yt-dlp --print`, where each result is a name line, an url line,
+/// then a line of [JsonEntry] metadata.
+fn parse_printed(output: &str) -> Vec<SearchResult> {
+    let mut iter = output.split('\n');
     let mut results = Vec::new();
 
-    while let (Some(name), Some(url)) = (iter.next(), iter.next()) {
+    while let (Some(name), Some(url), Some(json)) = (iter.next(), iter.next(), iter.next()) {
+        // Missing metadata only means it has to be looked up later.
+        let entry = serde_json::from_str::<JsonEntry>(json).unwrap_or_default();
         results.push(SearchResult {
             name: name.to_string(),
             url: url.to_string(),
+            ..entry.into_result()
         });
     }
 
-    Ok(results)
+    results
+}
+
+/// The fields of a `youtube-dl --dump-json` entry used for a [SearchResult].
+/// `yt-dlp` prints some of the same fields as json, see [run].
+#[derive(Debug, Default, Deserialize)]
+struct JsonEntry {
+    /// Title of the video.
+    title: Option<String>,
+    /// Duration in seconds.
+    duration: Option<f64>,
+    /// Name of the uploader.
+    uploader: Option<String>,
+    /// Name of the channel, preferred over `uploader`.
+    channel: Option<String>,
+    /// Url of the thumbnail, missing from flat entries.
+    thumbnail: Option<String>,
+    /// Every thumbnail, smallest first.
+    #[serde(default)]
+    thumbnails: Vec<Thumbnail>,
+    /// Full url of the video, missing from flat entries.
+    webpage_url: Option<String>,
+    /// Flat entries only have the video id here.
+    url: Option<String>,
+}
+
+/// One of the [JsonEntry::thumbnails].
+#[derive(Debug, Deserialize)]
+struct Thumbnail {
+    /// Where the thumbnail is.
+    url: String,
+}
+
+impl JsonEntry {
+    /// The metadata of a [SearchResult], without its name and url.
+    fn into_result(self) -> SearchResult {
+        let thumbnail = self
+            .thumbnail
+            .or_else(|| self.thumbnails.into_iter().last().map(|thumb| thumb.url));
+        SearchResult {
+            title: self.title,
+            duration: self
+                .duration
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64),
+            channel: self.channel.or(self.uploader),
+            thumbnail,
+            ..Default::default()
+        }
+    }
+}
+
+/// Parse the output of `youtube-dl --dump-json`, where each line is one result.
+fn parse_json(output: &str) -> Vec<SearchResult> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<JsonEntry>(line).ok())
+        .filter_map(|mut entry| {
+            let url = match (entry.webpage_url.take(), entry.url.take()) {
+                (Some(url), _) => url,
+                (None, Some(url)) if url.starts_with("http") => url,
+                (None, Some(id)) => format!("https://www.youtube.com/watch?v={id}"),
+                (None, None) => return None,
+            };
+            let result = entry.into_result();
+            // Same budget as the `yt-dlp` format, minus the view count.
+            let title: String = result
+                .title
+                .clone()
+                .unwrap_or_default()
+                .chars()
+                .take(60)
+                .collect();
+            let duration = result
+                .duration
+                .map(|duration| lib::format_duration(&duration))
+                .unwrap_or_default();
+            let uploader: String = result
+                .channel
+                .clone()
+                .unwrap_or_default()
+                .chars()
+                .take(14)
+                .collect();
+            Some(SearchResult {
+                name: format!("{title} {duration} - {uploader}"),
+                url,
+                ..result
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_ytdlp_errors() {
+        let cases = [
+            (
+                "ERROR: [youtube] abc: The uploader has not made this video available in your country",
+                Some(Unavailable::GeoRestricted),
+            ),
+            (
+                "ERROR: [youtube] abc: Sign in to confirm your age. This video may be inappropriate for some users.",
+                Some(Unavailable::AgeRestricted),
+            ),
+            (
+                "ERROR: [youtube] abc: Private video. Sign in if you've been granted access to this video",
+                Some(Unavailable::Private),
+            ),
+            (
+                "ERROR: [youtube] abc: Video unavailable. This video has been removed by the uploader",
+                Some(Unavailable::Private),
+            ),
+            (
+                "ERROR: [youtube] abc: Unable to download webpage: <urlopen error [Errno -3] Temporary failure in name resolution>",
+                Some(Unavailable::Network),
+            ),
+            (
+                "ERROR: Unsupported URL: https://example.com/",
+                Some(Unavailable::UnsupportedUrl),
+            ),
+            ("ERROR: something nobody has seen before", None),
+        ];
+
+        for (stderr, expected) in cases {
+            assert_eq!(classify(stderr), expected, "{stderr}");
+        }
+    }
+
+    #[test]
+    fn cleans_error_lines() {
+        assert_eq!(
+            clean_error("\nERROR: [youtube] abc: Requested format is not available\nmore")
+                .as_deref(),
+            Some("Requested format is not available")
+        );
+        assert_eq!(
+            clean_error("ERROR: Something broke").as_deref(),
+            Some("Something broke")
+        );
+        assert_eq!(clean_error(" \n"), None);
+    }
+
+    #[test]
+    fn parses_printed_metadata() {
+        let output = concat!(
+            "Song [3:25] (1M views)- Artist\n",
+            "https://www.youtube.com/watch?v=a\n",
+            r#"{"title": "Song", "duration": 205, "channel": "Artist", "thumbnails": [{"url": "small"}, {"url": "big"}]}"#,
+            "\n",
+            "Other [1:00] (5 views)- Someone\n",
+            "https://www.youtube.com/watch?v=b\n",
+            "NA\n",
+        );
+
+        let results = parse_printed(output);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url, "https://www.youtube.com/watch?v=a");
+        assert_eq!(results[0].title.as_deref(), Some("Song"));
+        assert_eq!(results[0].duration, Some(Duration::from_secs(205)));
+        assert_eq!(results[0].channel.as_deref(), Some("Artist"));
+        assert_eq!(results[0].thumbnail.as_deref(), Some("big"));
+        // Unreadable metadata still leaves the result itself.
+        assert_eq!(results[1].name, "Other [1:00] (5 views)- Someone");
+        assert_eq!(results[1].title, None);
+    }
+
+    #[tokio::test]
+    async fn repeated_searches_are_cached() {
+        use std::sync::atomic::AtomicUsize;
+        use std::sync::atomic::Ordering;
+
+        let cache = Mutex::new(SearchCache::new(2));
+        let calls = AtomicUsize::new(0);
+        let search = |query: &'static str, ttl| {
+            let key = CacheKey::new(query, 5, Backend::YtDlp);
+            SearchCache::get_or_search(&cache, key, ttl, || async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(vec![SearchResult {
+                    name: query.to_string(),
+                    ..Default::default()
+                }])
+            })
+        };
+        let ttl = Duration::from_secs(60);
+
+        search("never gonna", ttl).await.unwrap();
+        // Normalized to the same query.
+        let cached = search("  Never   gonna ", ttl).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cached[0].name, "never gonna");
+
+        // An expired entry is searched again.
+        search("never gonna", Duration::ZERO).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Past capacity, the least recently used entry is evicted.
+        search("a", ttl).await.unwrap();
+        search("b", ttl).await.unwrap();
+        search("c", ttl).await.unwrap();
+        search("a", ttl).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+        search("c", ttl).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 6);
+    }
 }
@@ -1,22 +1,31 @@
 //! Logging functionality and error reporting.
 //! The logging library of choice is [tracing].
 
-use bon::builder;
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
 use itertools::Itertools;
 use poise::BoxFuture;
 use poise::CreateReply;
 use poise::FrameworkError;
+use serenity::CreateEmbed;
 use serenity::CreateMessage;
+use tokio::sync::Semaphore;
 use tracing::debug;
 use tracing::error;
 use tracing::level_filters::LevelFilter;
+use tracing::warn;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
-    filter::Targets, fmt, layer::SubscriberExt, util::SubscriberInitExt, Layer,
+    filter::Targets, fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, Registry,
 };
 
 use crate::error::UserError;
 use crate::serenity;
+use crate::setup;
+use crate::setup::LogRotation;
+use crate::setup::NotifyVerbosity;
 use crate::Config;
 use crate::Context;
 use crate::Data;
@@ -25,20 +34,66 @@ use crate::ParakeetError;
 /// The name of this crate, used to set filter target.
 const THIS_CRATE: &str = env!("CARGO_CRATE_NAME");
 
+/// The least time between re-registering commands, so discord's api isn't hammered.
+const REREGISTER_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+
+/// Time between messages sent in bulk, on top of serenity's own rate limiting.
+const BULK_SEND_SPACING: Duration = Duration::from_millis(250);
+
+/// Discord allows embed descriptions of at most this many chars.
+const MAX_EMBED_LEN: usize = 4096;
+
+/// Only one batch of bug notifications is sent at a time, so a burst of errors queues up
+/// instead of flooding discord.
+static NOTIFY_PERMITS: Semaphore = Semaphore::const_new(1);
+
+/// Handle to change which traces are tracked while the bot is running, see [set_level].
+pub type LogHandle = reload::Handle<Targets, Registry>;
+
+/// Bug notifications sent recently, keyed by the error they were about.
+/// Used to hold back identical errors, see [Config::notify_repeat_window].
+#[derive(Debug, Default)]
+pub struct NotifyHistory {
+    /// When a notification about each error was last sent,
+    /// and how many times it happened again since then.
+    sent: HashMap<String, (Instant, usize)>,
+}
+
+impl NotifyHistory {
+    /// Whether a notification about `error` should be sent, given how recently one was.
+    /// Returns `None` if it was sent within `window`, otherwise how many times it was held back.
+    fn check(&mut self, error: &str, window: Duration) -> Option<usize> {
+        // Forget errors that weren't held back, so the map doesn't keep growing.
+        self.sent
+            .retain(|_, (sent, repeats)| *repeats > 0 || sent.elapsed() < window);
+
+        match self.sent.get_mut(error) {
+            Some((sent, repeats)) if sent.elapsed() < window => {
+                *repeats += 1;
+                None
+            }
+            _ => {
+                let previous = self.sent.insert(error.to_string(), (Instant::now(), 0));
+                Some(previous.map_or(0, |(_, repeats)| repeats))
+            }
+        }
+    }
+}
+
 /// Setup format layers, tracing subscribers, and installs tracing.
-pub(super) fn install_tracing(config: &Config) -> Option<WorkerGuard> {
+pub(super) fn install_tracing(config: &Config) -> (Option<WorkerGuard>, LogHandle) {
     // Uses local time.
     let timer = fmt::time::ChronoLocal::rfc_3339();
 
     // Set which traces are tracked.
     // By default, all INFO traces and above are shown.
-    let target = if config.console_debug() {
-        Targets::new()
-            .with_default(LevelFilter::INFO)
-            .with_target(THIS_CRATE, LevelFilter::DEBUG)
+    let (target, invalid_targets) = if config.console_debug() {
+        targets(config, LevelFilter::INFO, LevelFilter::DEBUG)
     } else {
-        Targets::new().with_default(LevelFilter::INFO)
+        targets(config, LevelFilter::INFO, LevelFilter::INFO)
     };
+    // Shared by every layer, so it can be swapped out at runtime.
+    let (target, handle) = reload::Layer::new(target);
 
     // Compose the layer that prints traces to stdout
     let console_layer = if config.console_debug() {
@@ -51,7 +106,6 @@ pub(super) fn install_tracing(config: &Config) -> Option<WorkerGuard> {
             .with_target(true)
             .with_timer(timer.clone())
             .pretty()
-            .with_filter(target.clone())
     } else {
         // Default layer
         fmt::layer()
@@ -62,7 +116,6 @@ pub(super) fn install_tracing(config: &Config) -> Option<WorkerGuard> {
             .with_target(true)
             .with_timer(timer.clone())
             .pretty()
-            .with_filter(target.clone())
     };
 
     // Compose the layer that writes logs and get a guard for the writer.
@@ -71,9 +124,15 @@ pub(super) fn install_tracing(config: &Config) -> Option<WorkerGuard> {
         // Get the directory to store logs.
         let dir = config.log_dir();
 
-        // Put file logs in `log_dir` directory as "{THIS_CRATE}.log.{TIMESTAMP}" on an hourly basis.
+        // Put file logs in `log_dir` directory as "{THIS_CRATE}.log.{TIMESTAMP}",
+        // starting a new file as often as configured (hourly by default).
         let prefix_format = format!("{THIS_CRATE}.log");
-        let appender = tracing_appender::rolling::hourly(dir, prefix_format);
+        let appender = match config.log_rotation() {
+            LogRotation::Minutely => tracing_appender::rolling::minutely(dir, prefix_format),
+            LogRotation::Hourly => tracing_appender::rolling::hourly(dir, prefix_format),
+            LogRotation::Daily => tracing_appender::rolling::daily(dir, prefix_format),
+            LogRotation::Never => tracing_appender::rolling::never(dir, prefix_format),
+        };
 
         // Create the writer and writer guard.
         let (writer, guard) = tracing_appender::non_blocking(appender);
@@ -90,7 +149,6 @@ pub(super) fn install_tracing(config: &Config) -> Option<WorkerGuard> {
                 .with_timer(timer)
                 .with_writer(writer)
                 .compact()
-                .with_filter(target)
         } else {
             // Default layer
             fmt::layer()
@@ -102,7 +160,6 @@ pub(super) fn install_tracing(config: &Config) -> Option<WorkerGuard> {
                 .with_timer(timer)
                 .with_writer(writer)
                 .compact()
-                .with_filter(target)
         };
 
         (Some(layer), Some(guard))
@@ -112,18 +169,62 @@ pub(super) fn install_tracing(config: &Config) -> Option<WorkerGuard> {
 
     // Add all the layers and initialize them.
     tracing_subscriber::registry()
+        .with(target)
         .with(console_layer)
         .with(log_layer)
         .init();
 
-    guard
+    for invalid in invalid_targets {
+        warn!("Ignoring invalid log level in config: '{invalid}'");
+    }
+
+    (guard, handle)
+}
+
+/// Build the filter for which traces are tracked, with the configured per-target levels on top.
+/// Also returns the configured targets that have an invalid level.
+fn targets(
+    config: &Config,
+    default: LevelFilter,
+    this_crate: LevelFilter,
+) -> (Targets, Vec<String>) {
+    let mut target = Targets::new()
+        .with_default(default)
+        .with_target(THIS_CRATE, this_crate);
+
+    let mut invalid_targets = Vec::new();
+    for (name, level) in config.log_targets() {
+        match level.parse::<LevelFilter>() {
+            Ok(level) => target = target.with_target(name, level),
+            Err(_) => invalid_targets.push(format!("{name} = {level}")),
+        }
+    }
+
+    (target, invalid_targets)
+}
+
+/// Change the level of traces tracked by default and in this crate, until the next restart.
+/// The per-target levels from the config still apply.
+pub fn set_level(
+    handle: &LogHandle,
+    config: &Config,
+    level: LevelFilter,
+) -> Result<(), ParakeetError> {
+    let (target, _) = targets(config, level, level);
+    handle.reload(target)?;
+    Ok(())
 }
 
 /// Defines various behaviors for how to handle errors.
-/// Triggers an [ephemeral_reply] on [ParakeetError::UserError].
+/// Triggers a [send_reply] on [ParakeetError::UserError].
 /// Triggers a [notify_bug] on unexpected errors.
 pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFuture<()> {
     let handler = async move {
+        // Failed commands never reach `post_command`, so stop timing them here.
+        if let Some(ctx) = err.ctx() {
+            ctx.data().command_starts.lock().await.remove(&ctx.id());
+        }
+
         match err {
             // ---
             // This section includes all errors that should be invisible to users.
@@ -157,7 +258,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -172,7 +273,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
             } => {
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -185,7 +286,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .add_info(error.to_string())
                     .build()
@@ -201,7 +302,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -218,7 +319,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -235,7 +336,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -246,7 +347,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -257,7 +358,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -268,7 +369,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -279,7 +380,7 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
 
                 Response::builder()
                     .ctx(&ctx)
-                    .reply(user_error.to_string())
+                    .reply(user_message(&ctx, &user_error))
                     .source(user_error)
                     .build()
                     .send()
@@ -345,6 +446,26 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
                 .build()
                 .send()
                 .await;
+
+                // A mismatch usually means the registered commands are stale.
+                reregister(&ctx.into()).await;
+            }
+
+            // Only reachable with text commands enabled, a typo isn't worth an error.
+            FrameworkError::UnknownCommand {
+                ctx,
+                msg,
+                prefix,
+                msg_content,
+                framework,
+                ..
+            } if framework.user_data.config.command_prefix().is_some() => {
+                let name = msg_content.split_whitespace().next().unwrap_or_default();
+                debug!("Unknown text command '{name}' from {}.", msg.author);
+                let reply = format!("I don't know `{prefix}{name}`, try `/` to see my commands.");
+                if let Err(e) = msg.reply(ctx, reply).await {
+                    error!("Failed to send reply. {e}")
+                }
             }
 
             // ---
@@ -373,25 +494,85 @@ pub fn handle_framework_error(err: FrameworkError<Data, ParakeetError>) -> BoxFu
     Box::pin(handler)
 }
 
-/// Sends an ephemeral reply to the [Context] author.
-async fn ephemeral_reply(ctx: &Context<'_>, content: impl Into<String>) {
-    let reply = CreateReply::default().ephemeral(true).content(content);
+/// Re-register the commands, at most once every [REREGISTER_COOLDOWN].
+async fn reregister(ctx: &Context<'_>) {
+    let data = ctx.data();
+    {
+        let mut last = data.last_reregister.lock().await;
+        if last.is_some_and(|last| last.elapsed() < REREGISTER_COOLDOWN) {
+            debug!("Commands were re-registered recently, skipping.");
+            return;
+        }
+        *last = Some(Instant::now());
+    }
+
+    warn!("Re-registering commands after a structure mismatch.");
+    if let Err(e) = setup::register_commands(ctx.serenity_context(), &data.config).await {
+        error!("Failed to re-register commands. {e}");
+    }
+}
+
+/// Sends a reply to the [Context] author, only visible to them if `ephemeral`.
+async fn send_reply(ctx: &Context<'_>, content: impl Into<String>, ephemeral: bool) {
+    let reply = CreateReply::default().ephemeral(ephemeral).content(content);
     if let Err(e) = ctx.send(reply).await {
-        error!("Failed to send ephemeral reply. {e}")
+        error!("Failed to send reply. {e}")
     };
 }
 
-/// Sends a notification (via private message) to users in [notify_bugs](crate::config::NotifyConfig).
-/// If message fails, only log and don't retry.
+/// Sends a notification (via private message) to users in [notify_bugs](crate::config::NotifyConfig),
+/// or as an embed in the [notify channel](Config::notify_channel) if there is one.
+/// If posting in the channel fails (e.g. missing permissions), private messages are sent instead.
+/// If a message fails, only log and don't retry.
+///
+/// Messages are sent in the background, so a long notify list doesn't hold up the reply.
+/// Serenity already waits out rate limits per route, but opening dm channels has a limit
+/// it can't know ahead of time, so the messages are also spaced out by [BULK_SEND_SPACING]
+/// and only one batch is sent at a time (see [NOTIFY_PERMITS]).
 async fn notify_bug(ctx: &Context<'_>, content: impl Into<String>) {
-    let message = CreateMessage::new().content(content);
+    let content: String = content.into();
+    let http = ctx.serenity_context().http.clone();
+    let config = &ctx.data().config;
+    let channel = config.notify_channel();
+    let mut send_dms = config.notify_dm();
+    let notify_list = ctx.data().notify_list.clone();
+
+    tokio::spawn(async move {
+        let Ok(_permit) = NOTIFY_PERMITS.acquire().await else {
+            return;
+        };
+        if let Some(channel) = channel {
+            let description: String = content.chars().take(MAX_EMBED_LEN).collect();
+            let embed = CreateEmbed::new()
+                .title("Bug notification")
+                .description(description);
+            if let Err(e) = channel
+                .send_message(&http, CreateMessage::new().embed(embed))
+                .await
+            {
+                error!("Failed to post bug notification in {channel}, sending it privately. {e}");
+                send_dms = true;
+            }
+        }
+        if !send_dms {
+            return;
+        }
 
-    let notify_list = &ctx.data().notify_list;
-    for user in notify_list {
-        if let Err(e) = user.direct_message(ctx, message.clone()).await {
-            error!("Failed to send bug notification. {e}");
+        let message = CreateMessage::new().content(content);
+        for (i, user) in notify_list.into_iter().enumerate() {
+            if i > 0 {
+                tokio::time::sleep(BULK_SEND_SPACING).await;
+            }
+            if let Err(e) = user.direct_message(&http, message.clone()).await {
+                error!("Failed to send bug notification. {e}");
+            }
         }
-    }
+    });
+}
+
+/// Display a [UserError], using the configured message override if there is one.
+fn user_message(ctx: &Context, error: &UserError) -> String {
+    error.display_with(ctx.data().config.messages())
 }
 
 /// Helper function to create debug information from [Context]
@@ -402,6 +583,25 @@ fn debug_info(ctx: &Context) -> String {
     format!("{user} tried to use {cmd} with {user_input}.")
 }
 
+/// Format a bug notification with as much detail as `verbosity` allows.
+fn notify_content(
+    verbosity: NotifyVerbosity,
+    ctx: &Context,
+    source: &ParakeetError,
+    log_message: &str,
+) -> String {
+    let user = &ctx.author().name;
+    let cmd = &ctx.command().name;
+    match verbosity {
+        NotifyVerbosity::Full => {
+            let dbg_info = debug_info(ctx);
+            format!("Debug Info: {dbg_info}\n{log_message}")
+        }
+        NotifyVerbosity::Redacted => format!("Debug Info: {user} tried to use {cmd}.\n{source}"),
+        NotifyVerbosity::Minimal => format!("An error occurred in `/{cmd}`, check the logs."),
+    }
+}
+
 /// Structured response to errors.
 /// Always logs as at least [debug level](tracing::debug), but is upgraded to
 /// [error level](tracing::error) if `error` is `Some(...)`.
@@ -414,8 +614,11 @@ struct Response<'a> {
     /// The reason for this reply, usually the error causing the response.
     #[builder(into)]
     source: ParakeetError,
-    /// Optional ephemeral reply to user.
+    /// Optional reply to user.
     reply: Option<String>,
+    /// Set to `false` to make the reply visible to the whole channel.
+    /// By default, only [UserError]s are visible to everyone, if [Config::public_errors] is set.
+    ephemeral: Option<bool>,
     /// Additional information to log
     add_info: Option<String>,
     /// Set to `true` to log as error.
@@ -446,18 +649,62 @@ impl Response<'_> {
             if self.notify {
                 // Construct and send notification message
 
-                let dbg_info = debug_info(ctx);
-                // Format of message
-                let content = format!("Debug Info: {dbg_info}\n{log_message}");
-                notify_bug(ctx, content).await;
+                let config = &ctx.data().config;
+                let mut content =
+                    notify_content(config.notify_verbosity(), ctx, &self.source, &log_message);
+
+                // Identical errors are only counted until the window passes.
+                let repeats = match config.notify_repeat_window() {
+                    Some(window) => {
+                        let mut history = ctx.data().notify_history.lock().await;
+                        history.check(&self.source.to_string(), window)
+                    }
+                    None => Some(0),
+                };
+                match repeats {
+                    None => debug!("Holding back a repeated bug notification."),
+                    Some(repeats) => {
+                        if repeats > 0 {
+                            content.push_str(&format!(
+                                "\n(Also happened {repeats} more times since the last notification)"
+                            ));
+                        }
+                        notify_bug(ctx, content).await;
+                    }
+                }
             }
         } else {
             debug!("{log_message}");
         }
 
-        // Send ephemeral reply if there is one.
+        // Send reply if there is one.
         if let Some(ref reply) = self.reply {
-            ephemeral_reply(ctx, reply).await;
+            // Anything worth a bug report may leak internal details, so it's always kept private.
+            let is_user_error = matches!(self.source, ParakeetError::UserError(_));
+            let public = ctx.data().config.public_errors() && is_user_error && !self.is_error;
+            let ephemeral = self.ephemeral.unwrap_or(!public);
+            send_reply(ctx, reply, ephemeral).await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_back_repeated_notifications() {
+        let mut history = NotifyHistory::default();
+        let window = Duration::from_secs(60);
+
+        assert_eq!(history.check("yt-dlp broke", window), Some(0));
+        assert_eq!(history.check("yt-dlp broke", window), None);
+        assert_eq!(history.check("yt-dlp broke", window), None);
+        // Other errors aren't held back.
+        assert_eq!(history.check("something else", window), Some(0));
+
+        // Once the window passes, the repeats are reported.
+        assert_eq!(history.check("yt-dlp broke", Duration::ZERO), Some(2));
+        assert_eq!(history.check("yt-dlp broke", Duration::ZERO), Some(0));
+    }
+}
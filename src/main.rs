@@ -2,6 +2,7 @@
 #![warn(nonstandard_style)]
 #![warn(clippy::missing_docs_in_private_items)]
 #![allow(special_module_name)]
+#![allow(clippy::result_large_err)]
 
 mod commands;
 mod data;
@@ -27,10 +28,17 @@ async fn main() -> Result<(), ParakeetError> {
     // Read config file.
     let config = Config::read()?;
     // Initialize logging.
-    let _tracing_guard = log::install_tracing(&config);
+    let (tracing_guard, log_handle) = log::install_tracing(&config);
+    let state_dir = config.persistence_dir().map(str::to_string);
 
-    let mut client = setup::client(config).await?;
-    client.start().await?;
+    let mut client = setup::client(config, log_handle).await?;
+    tokio::select! {
+        result = client.start() => result?,
+        () = setup::shutdown_signal() => setup::shutdown(&client, state_dir.as_deref()).await,
+    }
+
+    // Flush buffered logs before exiting.
+    drop(tracing_guard);
 
     Ok(())
 }
@@ -1,19 +1,27 @@
 //! Configuration for running this bot.
 
+use std::collections::HashMap;
 use std::collections::HashSet;
+use std::time::Duration;
 
 use poise::Framework;
 use serde::Deserialize;
 use serde::Serialize;
+use serenity::ChannelId;
 use serenity::GuildId;
 use serenity::UserId;
 
 use crate::error::ConfigError;
+use crate::lib::youtube::Backend;
+use crate::lib::youtube::SearchStrategy;
 use crate::serenity;
 
 /// The path to the config file
 const CONFIG_PATH: &str = "config.toml";
 
+/// The loudest [Config::default_volume] allowed, in percent.
+const MAX_DEFAULT_VOLUME: u16 = 200;
+
 /// Settings read from [CONFIG_PATH] that modify bot behavior.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
@@ -25,6 +33,47 @@ pub struct Config {
 
     /// Useful developer specific configs.
     dev_utils: DevConfig,
+
+    /// See [PlaybackConfig]
+    #[serde(default)]
+    playback: PlaybackConfig,
+
+    /// See [PersistenceConfig]
+    #[serde(default)]
+    persistence: PersistenceConfig,
+
+    /// See [AutocompleteConfig]
+    #[serde(default)]
+    autocomplete: AutocompleteConfig,
+
+    /// Overrides for user-facing error messages, keyed by [UserError::key](crate::error::UserError::key).
+    #[serde(default)]
+    messages: HashMap<String, String>,
+
+    /// Overrides for command descriptions, keyed by command name.
+    #[serde(default)]
+    command_descriptions: HashMap<String, String>,
+
+    /// Show user errors (e.g. bad arguments) to the whole channel instead of just the user.
+    #[serde(default)]
+    public_errors: bool,
+
+    /// Shown in place of a track's title if it doesn't have one, e.g. "Unknown Track".
+    #[serde(default)]
+    missing_title: Option<String>,
+
+    /// Serve prometheus metrics on this port, disabled if unset.
+    #[serde(default)]
+    metrics_port: Option<u16>,
+
+    /// Also accept commands as messages starting with this (e.g. `!play`), disabled if unset.
+    /// Needs the privileged message content intent.
+    #[serde(default)]
+    command_prefix: Option<String>,
+
+    /// Register `/lyrics`, which looks lyrics up with an external api.
+    #[serde(default)]
+    lyrics: bool,
 }
 
 impl Config {
@@ -51,9 +100,11 @@ impl Config {
                     let to_toml = toml::Deserializer::new(&content);
                     let result: Result<Config, _> = serde_path_to_error::deserialize(to_toml);
 
-                    result.map_err(|error| ConfigError::InvalidConfig {
+                    let config = result.map_err(|error| ConfigError::InvalidConfig {
                         reason: error.to_string(),
-                    })
+                    })?;
+                    config.validate()?;
+                    Ok(config)
                 }
             }
             // File not found or other filesystem error
@@ -72,6 +123,16 @@ impl Config {
         }
     }
 
+    /// Check settings that depend on each other, which deserializing alone can't.
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.registration() == Registration::Guild && self.dev_guild().is_none() {
+            Err(ConfigError::InvalidConfig {
+                reason: "Guild registration needs a dev_guild".to_string(),
+            })?
+        }
+        Ok(())
+    }
+
     /// Basic sanity check for if a token was given.
     pub fn token(&self) -> Result<&String, ConfigError> {
         let default_token = Config::default().discord_token;
@@ -97,6 +158,32 @@ impl Config {
         self.dev_utils.notifications.notify_list(fw)
     }
 
+    /// How much detail bug notifications include.
+    pub fn notify_verbosity(&self) -> NotifyVerbosity {
+        self.dev_utils.notifications.verbosity
+    }
+
+    /// The channel bug notifications are posted in, if any.
+    pub fn notify_channel(&self) -> Option<ChannelId> {
+        let notifications = &self.dev_utils.notifications;
+        notifications
+            .enabled
+            .then_some(notifications.channel)
+            .flatten()
+    }
+
+    /// Whether bug notifications are sent as private messages, see [Config::notify_list].
+    /// Without a [notify channel](Config::notify_channel), they always are.
+    pub fn notify_dm(&self) -> bool {
+        self.notify_channel().is_none() || self.dev_utils.notifications.also_dm
+    }
+
+    /// How long identical bug notifications are held back for, if at all.
+    pub fn notify_repeat_window(&self) -> Option<Duration> {
+        let secs = self.dev_utils.notifications.repeat_window_secs;
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
     /// Getter for log_dir.
     /// TODO: Path validation?
     pub fn log_dir(&self) -> &str {
@@ -113,9 +200,200 @@ impl Config {
         self.logging.logs_enabled
     }
 
+    /// Log levels for specific targets, as written in the config.
+    pub fn log_targets(&self) -> &HashMap<String, String> {
+        &self.logging.targets
+    }
+
+    /// How command starts and finishes are logged.
+    pub fn command_log(&self) -> CommandLog {
+        self.logging.commands
+    }
+
+    /// How often a new log file is started.
+    pub fn log_rotation(&self) -> LogRotation {
+        self.logging.log_rotation
+    }
+
+    /// Whether finished commands are logged with how long they took.
+    pub fn command_timing(&self) -> bool {
+        self.logging.command_timing
+    }
+
     pub fn dev_guild(&self) -> Option<GuildId> {
         self.dev_utils.dev_guild
     }
+
+    /// Where commands are registered, see [Registration].
+    /// [Registration::Guild] always comes with a [dev_guild](Config::dev_guild).
+    pub fn registration(&self) -> Registration {
+        self.dev_utils.registration
+    }
+
+    /// Whether commands are registered on startup even if they haven't changed.
+    pub fn force_register(&self) -> bool {
+        self.dev_utils.force_register
+    }
+
+    /// How long a `/preview` plays for.
+    pub fn preview_duration(&self) -> Duration {
+        Duration::from_secs(self.playback.preview_secs)
+    }
+
+    /// The volume guilds start at, where `1.0` is unchanged.
+    /// Clamped to [MAX_DEFAULT_VOLUME] percent.
+    pub fn default_volume(&self) -> f32 {
+        f32::from(self.playback.default_volume.min(MAX_DEFAULT_VOLUME)) / 100.0
+    }
+
+    /// Tracks that end on their own quicker than this are likely broken.
+    pub fn fast_end_threshold(&self) -> Duration {
+        Duration::from_millis(self.playback.fast_end_ms)
+    }
+
+    /// Whether idling also requires the queue to be empty.
+    pub fn idle_requires_empty_queue(&self) -> bool {
+        self.playback.idle_requires_empty_queue
+    }
+
+    /// How long playback can stay paused with members present before leaving, if enabled.
+    pub fn paused_timeout(&self) -> Option<Duration> {
+        let secs = self.playback.paused_timeout_secs;
+        (secs > 0).then(|| Duration::from_secs(secs))
+    }
+
+    /// Whether the bot deafens itself when joining voice.
+    pub fn self_deafen(&self) -> bool {
+        self.playback.self_deafen
+    }
+
+    /// How many tracks a guild's queue can hold by default.
+    pub fn max_queue_len(&self) -> usize {
+        self.playback.max_queue_len
+    }
+
+    /// The preferred program for searches.
+    pub fn search_backend(&self) -> Backend {
+        self.playback.search_backend
+    }
+
+    /// How long search results are reused for the same query.
+    pub fn search_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.playback.search_cache_secs)
+    }
+
+    /// The most search results `/playall` can add at once.
+    pub fn max_batch(&self) -> u8 {
+        self.playback.max_batch
+    }
+
+    /// How `/play` handles search queries by default.
+    pub fn search_strategy(&self) -> SearchStrategy {
+        self.playback.search_strategy
+    }
+
+    /// Whether only users in the bot's voice channel can control playback.
+    pub fn require_same_channel(&self) -> bool {
+        self.playback.require_same_channel
+    }
+
+    /// Whether rejoining after a disconnect plays what was left in the queue.
+    pub fn rejoin_resumes_queue(&self) -> bool {
+        self.playback.rejoin_resumes_queue
+    }
+
+    /// Whether an empty `/play` box suggests recently played tracks.
+    pub fn suggest_recent(&self) -> bool {
+        self.playback.suggest_recent
+    }
+
+    /// Whether each track is announced as it starts.
+    pub fn announce_tracks(&self) -> bool {
+        self.playback.announce_tracks
+    }
+
+    /// Whether playlists added with `/play` are shuffled by default.
+    pub fn auto_shuffle_playlists(&self) -> bool {
+        self.playback.auto_shuffle_playlists
+    }
+
+    /// Whether anyone can use the "Show queue" button on a `/play` reply.
+    pub fn queue_button_anyone(&self) -> bool {
+        self.playback.queue_button_anyone
+    }
+
+    /// The sound played when joining a voice channel, before the queue starts, if any.
+    pub fn join_sound_path(&self) -> Option<&str> {
+        self.playback.join_sound_path.as_deref()
+    }
+
+    /// The message sent when joining a voice channel, if any.
+    pub fn join_message(&self) -> Option<&str> {
+        self.playback.join_message.as_deref()
+    }
+
+    /// The directory of sound clips for `/sound`, if enabled.
+    pub fn sounds_dir(&self) -> Option<&str> {
+        self.playback.sounds_dir.as_deref()
+    }
+
+    /// Overrides for user-facing error messages.
+    pub fn messages(&self) -> &HashMap<String, String> {
+        &self.messages
+    }
+
+    /// Whether user errors are visible to the whole channel.
+    pub fn public_errors(&self) -> bool {
+        self.public_errors
+    }
+
+    /// What to show for tracks without a title, if overridden.
+    pub fn missing_title(&self) -> Option<&str> {
+        self.missing_title.as_deref()
+    }
+
+    /// Where state like the last registered commands is kept, whether or not queues are saved.
+    pub fn state_dir(&self) -> &str {
+        &self.persistence.state_dir
+    }
+
+    /// Where queues are saved on shutdown and loaded from on startup, if enabled.
+    pub fn persistence_dir(&self) -> Option<&str> {
+        let persistence = &self.persistence;
+        persistence
+            .enabled
+            .then_some(persistence.state_dir.as_str())
+    }
+
+    /// Whether `/play` arguments are autocompleted at all.
+    pub fn autocomplete_enabled(&self) -> bool {
+        self.autocomplete.enabled
+    }
+
+    /// How long to wait for more typing before autocompleting a search.
+    pub fn autocomplete_debounce(&self) -> Duration {
+        Duration::from_millis(self.autocomplete.debounce_ms)
+    }
+
+    /// The prefix for text commands, if they're enabled.
+    pub fn command_prefix(&self) -> Option<&str> {
+        self.command_prefix.as_deref()
+    }
+
+    /// The port metrics are served on, if enabled.
+    pub fn metrics_port(&self) -> Option<u16> {
+        self.metrics_port
+    }
+
+    /// Whether `/lyrics` is registered, since it calls an external api.
+    pub fn lyrics_enabled(&self) -> bool {
+        self.lyrics
+    }
+
+    /// Overrides for command descriptions.
+    pub fn command_descriptions(&self) -> &HashMap<String, String> {
+        &self.command_descriptions
+    }
 }
 
 impl Default for Config {
@@ -127,16 +405,46 @@ impl Default for Config {
                 console_debug: false,
                 logs_enabled: true,
                 log_dir: "logs".to_string(),
+                targets: HashMap::new(),
+                commands: CommandLog::default(),
+                command_timing: false,
+                log_rotation: LogRotation::default(),
             },
 
             dev_utils: DevConfig {
                 dev_guild: None,
+                registration: Registration::default(),
+                force_register: false,
                 notifications: NotifyConfig {
                     enabled: false,
                     add_owners: true,
                     userids: vec![],
+                    verbosity: NotifyVerbosity::default(),
+                    repeat_window_secs: default_repeat_window(),
+                    channel: None,
+                    also_dm: false,
                 },
             },
+
+            playback: PlaybackConfig::default(),
+
+            persistence: PersistenceConfig::default(),
+
+            autocomplete: AutocompleteConfig::default(),
+
+            messages: HashMap::new(),
+
+            command_descriptions: HashMap::new(),
+
+            public_errors: false,
+
+            missing_title: None,
+
+            metrics_port: None,
+
+            command_prefix: None,
+
+            lyrics: false,
         }
     }
 }
@@ -178,6 +486,160 @@ struct LoggingConfig {
     logs_enabled: bool,
     /// Directory to store log files
     log_dir: String,
+    /// Log levels for specific targets (e.g. `songbird = "debug"`)
+    #[serde(default)]
+    targets: HashMap<String, String>,
+    /// How command starts and finishes are logged.
+    #[serde(default)]
+    commands: CommandLog,
+    /// Include how long each command took when it finishes.
+    #[serde(default)]
+    command_timing: bool,
+    /// How often a new log file is started.
+    #[serde(default)]
+    log_rotation: LogRotation,
+}
+
+/// How often a new log file is started.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    /// Every minute.
+    Minutely,
+    /// Every hour.
+    #[default]
+    Hourly,
+    /// Every day.
+    Daily,
+    /// Always write to the same file.
+    Never,
+}
+
+/// How command starts and finishes are logged.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandLog {
+    /// Don't log them at all.
+    Off,
+    /// Log them at the debug level.
+    Debug,
+    /// Log them at the info level.
+    #[default]
+    Info,
+}
+
+/// Configs for how tracks are played.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct PlaybackConfig {
+    /// How many seconds a `/preview` plays for.
+    preview_secs: u64,
+    /// Volume guilds start at, in percent from 0 to 200.
+    default_volume: u16,
+    /// Tracks that end on their own within this many milliseconds are treated as broken, 0 disables.
+    fast_end_ms: u64,
+    /// Only leave an empty channel once nothing is queued.
+    idle_requires_empty_queue: bool,
+    /// Leave after playback has been paused for this many seconds, even with members present.
+    /// 0 disables this, so only an empty channel is left.
+    paused_timeout_secs: u64,
+    /// Deafen the bot when joining voice, so it doesn't receive incoming audio.
+    self_deafen: bool,
+    /// How many tracks a guild's queue can hold, unless the guild overrides it.
+    max_queue_len: usize,
+    /// The preferred program for searches, the other one is used if it isn't installed.
+    search_backend: Backend,
+    /// How many seconds search results are reused for the same query, 0 disables caching.
+    search_cache_secs: u64,
+    /// The most search results `/playall` can add at once.
+    max_batch: u8,
+    /// How `/play` handles search queries, unless the guild overrides it.
+    search_strategy: SearchStrategy,
+    /// Only let users in the bot's voice channel control playback.
+    require_same_channel: bool,
+    /// Play what was left in the queue when rejoining after a disconnect, instead of clearing it.
+    rejoin_resumes_queue: bool,
+    /// Suggest recently played tracks when the `/play` box is empty.
+    suggest_recent: bool,
+    /// Post each track as it starts, in the channel tracks were last added from.
+    announce_tracks: bool,
+    /// Shuffle the tracks of a playlist added with `/play`, unless the guild overrides it.
+    auto_shuffle_playlists: bool,
+    /// Let anyone use the "Show queue" button on a `/play` reply, not just whoever played.
+    queue_button_anyone: bool,
+    /// Local audio file played when joining a voice channel.
+    join_sound_path: Option<String>,
+    /// Message sent when joining a voice channel.
+    join_message: Option<String>,
+    /// Directory of sound clips for `/sound`, which is disabled without one.
+    sounds_dir: Option<String>,
+}
+
+impl Default for PlaybackConfig {
+    fn default() -> Self {
+        Self {
+            preview_secs: 10,
+            default_volume: 100,
+            fast_end_ms: 500,
+            idle_requires_empty_queue: false,
+            paused_timeout_secs: 0,
+            self_deafen: true,
+            max_queue_len: 100,
+            search_backend: Backend::default(),
+            search_cache_secs: 300,
+            max_batch: 10,
+            search_strategy: SearchStrategy::default(),
+            require_same_channel: true,
+            rejoin_resumes_queue: false,
+            suggest_recent: false,
+            announce_tracks: false,
+            auto_shuffle_playlists: false,
+            queue_button_anyone: true,
+            join_sound_path: None,
+            join_message: None,
+            sounds_dir: None,
+        }
+    }
+}
+
+/// Configs for keeping queues across restarts.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct PersistenceConfig {
+    /// Save queues on shutdown (ctrl-c) and load them on startup.
+    /// Loaded queues start playing again once the bot joins a channel.
+    enabled: bool,
+    /// Directory the queues and other state are saved in.
+    state_dir: String,
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            state_dir: "state".to_string(),
+        }
+    }
+}
+
+/// Configs for autocompleting `/play` arguments, which runs a search as the user types.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+struct AutocompleteConfig {
+    /// Autocomplete at all, disable if the searches are too expensive.
+    enabled: bool,
+    /// How many milliseconds to wait for more typing before searching.
+    /// Very low values start a search for nearly every keystroke, spawning many more processes.
+    debounce_ms: u64,
+}
+
+impl Default for AutocompleteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            debounce_ms: 600,
+        }
+    }
 }
 
 /// Optional configs to enable developer-specific behavior.
@@ -186,10 +648,28 @@ struct DevConfig {
     /// Optional guild to automatically update commands quickly.
     #[serde(serialize_with = "serialize_opt", deserialize_with = "deserialize_opt")]
     dev_guild: Option<GuildId>,
+    /// Where commands are registered, see [Registration].
+    #[serde(default)]
+    registration: Registration,
+    /// Always register commands on startup, even if they haven't changed.
+    #[serde(default)]
+    force_register: bool,
     /// See [NotifyConfig]
     notifications: NotifyConfig,
 }
 
+/// Where commands are registered.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Registration {
+    /// Everywhere, and on the dev guild too if there is one.
+    /// Global commands can take up to an hour to show up.
+    #[default]
+    Global,
+    /// Only on the dev guild, where commands show up right away.
+    Guild,
+}
+
 /// Configs for notification behavior when encountering unexpected errors.
 #[derive(Debug, Serialize, Deserialize)]
 struct NotifyConfig {
@@ -199,6 +679,37 @@ struct NotifyConfig {
     add_owners: bool,
     /// Additional users to add to the notify list.
     userids: Vec<UserId>,
+    /// How much detail notifications include, see [NotifyVerbosity].
+    #[serde(default)]
+    verbosity: NotifyVerbosity,
+    /// Identical errors within this many seconds of a notification are only counted,
+    /// and the count is added to the next one. 0 disables this.
+    #[serde(default = "default_repeat_window")]
+    repeat_window_secs: u64,
+    /// Post notifications in this channel instead of sending private messages.
+    #[serde(default)]
+    channel: Option<ChannelId>,
+    /// Also send private messages when posting in `channel`.
+    #[serde(default)]
+    also_dm: bool,
+}
+
+/// Default for [NotifyConfig::repeat_window_secs], also used when it's missing from the file.
+fn default_repeat_window() -> u64 {
+    60
+}
+
+/// How much detail a bug notification includes.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyVerbosity {
+    /// The user, their full input and the error with any additional info.
+    #[default]
+    Full,
+    /// The user, the command and the error, without user input or additional info.
+    Redacted,
+    /// Only the command that failed, details are left to the logs.
+    Minimal,
 }
 
 impl NotifyConfig {
@@ -237,6 +748,7 @@ fn write_file(config: Config) -> Result<(), ConfigError> {
     write(CONFIG_PATH, content).map_err(ConfigError::IoError)
 }
 
+/// Deserialize an empty string as `None`, otherwise parse a [GuildId].
 fn deserialize_opt<'de, D>(deserializer: D) -> Result<Option<GuildId>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -244,6 +756,7 @@ where
     deserializer.deserialize_str(OptVisitor)
 }
 
+/// Serialize `None` as an empty string, since toml has no null.
 fn serialize_opt<T, S>(val: &Option<T>, ser: S) -> Result<S::Ok, S::Error>
 where
     T: serde::Serialize,
@@ -255,6 +768,7 @@ where
     }
 }
 
+/// Visitor used by [deserialize_opt].
 struct OptVisitor;
 
 impl<'de> serde::de::Visitor<'de> for OptVisitor {
@@ -277,3 +791,34 @@ impl<'de> serde::de::Visitor<'de> for OptVisitor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_round_trips() {
+        let content = toml::to_string_pretty(&Config::default()).expect("serializes");
+        let config: Config = toml::from_str(&content).expect("deserializes");
+        assert_eq!(config.discord_token, Config::default().discord_token);
+    }
+
+    #[test]
+    fn missing_repeat_window_uses_default() {
+        let content = "enabled = true\nadd_owners = true\nuserids = []\n";
+        let notify: NotifyConfig = toml::from_str(content).expect("deserializes");
+        assert_eq!(notify.repeat_window_secs, default_repeat_window());
+    }
+
+    #[test]
+    fn guild_registration_needs_dev_guild() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+
+        config.dev_utils.registration = Registration::Guild;
+        assert!(config.validate().is_err());
+
+        config.dev_utils.dev_guild = Some(GuildId::new(1));
+        assert!(config.validate().is_ok());
+    }
+}
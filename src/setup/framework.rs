@@ -1,6 +1,26 @@
 //! Setup for [poise::Framework]
 
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use serenity::GuildId;
+use tokio::sync::Mutex;
+
+use super::config::CommandLog;
+use super::config::Registration;
 use crate::commands;
+use crate::data;
+use crate::data::GuildData;
+use crate::data::GuildDataKey;
+use crate::data::GuildDataRef;
+use crate::data::QueueMeta;
+use crate::lib::metrics;
+use crate::lib::metrics::METRICS;
+use crate::log::LogHandle;
 use crate::serenity;
 use crate::Config;
 use crate::Data;
@@ -9,71 +29,274 @@ use crate::ParakeetError;
 /// Convenient type alias, only this [poise::Framework] type is used.
 type Framework = poise::Framework<Data, ParakeetError>;
 
+/// The file in the [state directory](Config::state_dir) storing the last registered commands,
+/// see [registration_json].
+const REGISTERED_FILE: &str = "registered_commands.json";
+
+/// Command line flag to register commands even if they haven't changed.
+const FORCE_REGISTER_FLAG: &str = "--force-register";
+
+/// How many times registering commands is tried on startup.
+const REGISTER_ATTEMPTS: u32 = 3;
+
+/// How long to wait after the first failed registration, doubled after each failure.
+const REGISTER_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Discord allows command descriptions of at most this many chars.
+const MAX_DESCRIPTION_LEN: usize = 100;
+
 /// Construct a [poise::Framework]
-pub(super) fn framework(config: Config) -> Framework {
+pub(super) fn framework(config: Config, log_handle: LogHandle) -> Framework {
     poise::Framework::builder()
-        .options(framework_options())
-        .setup(|ctx, rdy, fw| framework_setup(ctx, rdy, fw, config))
+        .options(framework_options(&config))
+        .setup(|ctx, rdy, fw| framework_setup(ctx, rdy, fw, config, log_handle))
         .build()
 }
 
 /// Configure options for the [Framework]
-fn framework_options() -> poise::FrameworkOptions<Data, ParakeetError> {
+fn framework_options(config: &Config) -> poise::FrameworkOptions<Data, ParakeetError> {
     poise::FrameworkOptions {
         // Add commands to the framework
-        commands: crate::commands::list(),
+        commands: enabled_commands(config),
+        // Text commands are opt-in, mentioning the bot only works as a prefix if they're enabled.
+        prefix_options: poise::PrefixFrameworkOptions {
+            prefix: config.command_prefix().map(str::to_string),
+            mention_as_prefix: config.command_prefix().is_some(),
+            ..Default::default()
+        },
         // Handle framework errors
         on_error: |e| crate::log::handle_framework_error(e),
         // Log when commands start
         pre_command: |ctx| {
             Box::pin(async move {
+                let config = &ctx.data().config;
+                if config.command_timing() {
+                    let mut starts = ctx.data().command_starts.lock().await;
+                    starts.insert(ctx.id(), Instant::now());
+                }
+
                 let cmd_name = &ctx.command().name;
+                METRICS.command_invoked(cmd_name);
                 let user = &ctx.author();
-                tracing::info!("Started '{cmd_name}' command from {user}.")
+                log_command(
+                    config.command_log(),
+                    format!("Started '{cmd_name}' command from {user}."),
+                );
             })
         },
         // Log when finishing commands
         post_command: |ctx| {
             Box::pin(async move {
+                let config = &ctx.data().config;
+                let start = ctx.data().command_starts.lock().await.remove(&ctx.id());
+
                 let cmd_name = &ctx.command().name;
                 let user = &ctx.author();
-                tracing::info!("Finished '{cmd_name}' command from {user}.")
+                let msg = match start {
+                    Some(start) => format!(
+                        "Finished '{cmd_name}' command from {user} in {:.2?}.",
+                        start.elapsed()
+                    ),
+                    None => format!("Finished '{cmd_name}' command from {user}."),
+                };
+                log_command(config.command_log(), msg);
             })
         },
         ..Default::default()
     }
 }
 
+/// Log a command starting or finishing, see [CommandLog].
+fn log_command(level: CommandLog, msg: String) {
+    match level {
+        CommandLog::Off => {}
+        CommandLog::Debug => tracing::debug!("{msg}"),
+        CommandLog::Info => tracing::info!("{msg}"),
+    }
+}
+
+/// The commands to register, with their descriptions overridden by the config.
+/// Overrides for unknown commands or that are too long are ignored with a warning.
+fn registered_commands(config: &Config) -> Vec<commands::Command> {
+    let mut commands = enabled_commands(config);
+    for (name, description) in config.command_descriptions() {
+        let Some(command) = commands.iter_mut().find(|command| &command.name == name) else {
+            tracing::warn!("Description override for unknown command '{name}'.");
+            continue;
+        };
+        if description.chars().count() > MAX_DESCRIPTION_LEN {
+            tracing::warn!(
+                "Description override for '{name}' is longer than {MAX_DESCRIPTION_LEN} chars."
+            );
+            continue;
+        }
+        command.description = Some(description.clone());
+    }
+    commands
+}
+
+/// The commands that aren't turned off in `config`.
+fn enabled_commands(config: &Config) -> Vec<commands::Command> {
+    let mut commands = commands::list();
+    if !config.lyrics_enabled() {
+        commands.retain(|command| command.name != "lyrics");
+    }
+    commands
+}
+
+/// Where [REGISTERED_FILE] is kept.
+fn registered_path(config: &Config) -> PathBuf {
+    Path::new(config.state_dir()).join(REGISTERED_FILE)
+}
+
+/// The command definitions and where they are registered as json, used to detect changes.
+/// Compared as is, since std's hashers aren't stable across Rust releases.
+fn registration_json(app_commands: &[serenity::CreateCommand], config: &Config) -> String {
+    let registered = serde_json::json!({
+        "commands": app_commands,
+        "dev_guild": config.dev_guild(),
+        "registration": config.registration(),
+    });
+    registered.to_string()
+}
+
+/// Register the commands globally unless [Registration::Guild] is set, in which case the
+/// global commands are cleared instead, and on the dev guild if there is one.
+/// Also remembers what was registered, see [REGISTERED_FILE].
+pub async fn register_commands(
+    ctx: &serenity::Context,
+    config: &Config,
+) -> Result<(), ParakeetError> {
+    let commands = &registered_commands(config);
+    let app_commands = poise::builtins::create_application_commands(commands);
+    let registered = registration_json(&app_commands, config);
+
+    match config.registration() {
+        Registration::Global => {
+            tracing::info!("Registering commands.");
+            serenity::Command::set_global_commands(ctx, app_commands.clone()).await?;
+        }
+        Registration::Guild => {
+            // Commands registered globally before would show up twice on the dev guild.
+            tracing::info!("Clearing global commands.");
+            serenity::Command::set_global_commands(ctx, vec![]).await?;
+        }
+    }
+    if let Some(dev_guild) = config.dev_guild() {
+        // This is faster than global registers, useful for development.
+        tracing::info!("Registering commands on dev guild.");
+        dev_guild.set_commands(ctx, app_commands).await?;
+    }
+
+    let path = registered_path(config);
+    let written = std::fs::create_dir_all(config.state_dir())
+        .and_then(|()| std::fs::write(&path, registered));
+    if let Err(e) = written {
+        tracing::warn!("Failed to write {}: {e}", path.display());
+    }
+    Ok(())
+}
+
+/// Try [register_commands] a few times, waiting longer after each failure.
+/// Failing to register isn't fatal, the commands registered before keep working,
+/// and since what was registered isn't saved, registration is tried again on the next startup.
+async fn register_with_retry(ctx: &serenity::Context, config: &Config) {
+    let mut backoff = REGISTER_BACKOFF;
+    for attempt in 1..=REGISTER_ATTEMPTS {
+        match register_commands(ctx, config).await {
+            Ok(()) => return,
+            Err(e) if attempt < REGISTER_ATTEMPTS => {
+                tracing::warn!(
+                    "Registering commands failed (attempt {attempt}/{REGISTER_ATTEMPTS}), retrying in {backoff:?}. {e}"
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => tracing::error!(
+                "Failed to register commands after {REGISTER_ATTEMPTS} attempts, continuing with the ones already registered. {e}"
+            ),
+        }
+    }
+}
+
 /// Construct future that runs on startup
 fn framework_setup<'a>(
     ctx: &'a serenity::Context,
     rdy: &'a serenity::Ready,
     fw: &'a Framework,
     config: Config,
+    log_handle: LogHandle,
 ) -> poise::BoxFuture<'a, Result<Data, ParakeetError>> {
     Box::pin(async move {
         // Register the commands
-        let commands = &commands::list();
+        let commands = &registered_commands(&config);
         let app_commands = poise::builtins::create_application_commands(commands);
 
-        serenity::Command::set_global_commands(&ctx, app_commands.clone()).await?;
-        if let Some(dev_guild) = config.dev_guild() {
-            // This is faster than global registers, useful for development.
-            tracing::info!("Registering commands on dev guild.");
-            dev_guild.set_commands(ctx, app_commands).await?;
+        // Skip registering if nothing changed since the last startup.
+        let registered = registration_json(&app_commands, &config);
+        let force = config.force_register() || std::env::args().any(|a| a == FORCE_REGISTER_FLAG);
+        let last_registered = std::fs::read_to_string(registered_path(&config)).ok();
+
+        if force || last_registered.as_deref() != Some(registered.as_str()) {
+            register_with_retry(ctx, &config).await;
+        } else {
+            tracing::info!("Commands unchanged, skipping registration.");
         }
 
         // Simple message that logs when the bot has initialized
         let bot_name = &rdy.user.name;
         tracing::info!("{bot_name} is ready!");
 
+        if let Some(title) = config.missing_title() {
+            data::set_missing_title(title.to_string());
+        }
+        if let Some(port) = config.metrics_port() {
+            tokio::spawn(metrics::serve(port));
+        }
+
         let notify_list = config.notify_list(fw);
 
         let data = Data {
             notify_list,
+            log_handle: Some(log_handle),
+            guild_data: ctx
+                .data
+                .read()
+                .await
+                .get::<GuildDataKey>()
+                .cloned()
+                .expect("Expected guild data"),
+            config,
+            started: Instant::now(),
             ..Default::default()
         };
 
         Ok(data)
     })
 }
+
+/// Load the queues and queue limits saved on the last shutdown, if persistence is enabled.
+pub(super) fn restore_queues(config: &Config) -> Arc<Mutex<HashMap<GuildId, GuildDataRef>>> {
+    let Some(dir) = config.persistence_dir() else {
+        return Default::default();
+    };
+
+    let guild_data = data::persist::load_queues(dir)
+        .into_iter()
+        .map(|(guild_id, saved)| {
+            tracing::info!(
+                "Restored {} tracks for guild {guild_id}.",
+                saved.tracks.len()
+            );
+            let guild = GuildData {
+                restored_queue: !saved.tracks.is_empty(),
+                stale_queue: !saved.tracks.is_empty(),
+                queue_metadata: QueueMeta::restore(saved.tracks),
+                max_queue_len: saved.max_queue_len,
+                ..GuildData::new(config)
+            };
+            (guild_id, Arc::new(Mutex::new(guild)))
+        })
+        .collect();
+    Arc::new(Mutex::new(guild_data))
+}
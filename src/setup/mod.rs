@@ -2,26 +2,45 @@
 
 mod config;
 mod framework;
+mod shutdown;
 
 use songbird::SerenityInit;
 
+use crate::data::GuildDataKey;
 use crate::data::HttpKey;
+use crate::log::LogHandle;
 use crate::serenity;
 use crate::ParakeetError;
 
 pub use config::Config;
+pub use config::LogRotation;
+pub use config::NotifyVerbosity;
+pub use framework::register_commands;
+pub(super) use shutdown::shutdown;
+pub(super) use shutdown::shutdown_signal;
 
 /// Constructs a [serenity::Client] with initialized [songbird] and [reqwest::Client].
-pub(super) async fn client(config: Config) -> Result<serenity::Client, ParakeetError> {
+pub(super) async fn client(
+    config: Config,
+    log_handle: LogHandle,
+) -> Result<serenity::Client, ParakeetError> {
     // Get discord token from config file
     let token = config.token()?;
 
     // Intents we wish to use
     // See https://discord.com/developers/docs/topics/gateway#gateway-intents
-    let intents = serenity::GatewayIntents::non_privileged();
+    let mut intents = serenity::GatewayIntents::non_privileged();
+    // Text commands can't be read without the message content.
+    if config.command_prefix().is_some() {
+        intents |= serenity::GatewayIntents::MESSAGE_CONTENT;
+    }
+
+    // Kept outside the framework's data so queues can be saved on shutdown.
+    let guild_data = framework::restore_queues(&config);
 
     let client = serenity::ClientBuilder::new(token, intents)
-        .framework(framework::framework(config))
+        .type_map_insert::<GuildDataKey>(guild_data)
+        .framework(framework::framework(config, log_handle))
         .register_songbird()
         .type_map_insert::<HttpKey>(reqwest::Client::new())
         .await?;
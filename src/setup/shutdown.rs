@@ -0,0 +1,68 @@
+//! Leaves cleanly on Ctrl-C or SIGTERM, instead of dropping out of voice channels.
+
+use crate::data;
+use crate::data::GuildDataKey;
+use crate::serenity;
+
+/// Resolves once the process is asked to stop, by Ctrl-C or (on unix) SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("Can't listen for Ctrl-C. {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::signal;
+        use tokio::signal::unix::SignalKind;
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("Can't listen for SIGTERM. {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+}
+
+/// Save the queues to `state_dir` (if persistence is enabled),
+/// leave every voice channel and stop the shards.
+pub async fn shutdown(client: &serenity::Client, state_dir: Option<&str>) {
+    tracing::info!("Shutting down.");
+
+    let (guild_data, manager) = {
+        let type_map = client.data.read().await;
+        (
+            type_map.get::<GuildDataKey>().cloned(),
+            type_map.get::<songbird::SongbirdKey>().cloned(),
+        )
+    };
+
+    // Save before leaving, since leaving clears the queues.
+    if let (Some(dir), Some(guild_data)) = (state_dir, guild_data) {
+        data::persist::save_queues(&guild_data, dir).await;
+    }
+
+    if let Some(manager) = manager {
+        let guild_ids: Vec<_> = manager.iter().map(|(guild_id, _)| guild_id).collect();
+        for guild_id in guild_ids {
+            if let Err(e) = manager.remove(guild_id).await {
+                tracing::warn!("Failed to leave voice in guild {guild_id}. {e}");
+            }
+        }
+    }
+
+    client.shard_manager.shutdown_all().await;
+}